@@ -0,0 +1,112 @@
+use std::path::{Path, PathBuf};
+
+use crate::{git::split_ref, Data, Error, OutputFile};
+
+pub const ANSWERS_FILE_NAME: &str = ".tempura-answers.json";
+
+/// The inputs used to generate a project, saved in `.tempura-answers.json` in the output directory
+/// to regenerate (or update) the project later.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Answers {
+    /// The version of tempura that generated the project.
+    pub tempura: String,
+    pub templates: Vec<AnsweredTemplate>,
+    /// The `--delimiters` option (e.g. `"<% %>"`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delimiters: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub include_vcs: bool,
+    pub data: Data,
+}
+
+/// A template argument.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnsweredTemplate {
+    /// The template argument as given (relative paths are relative to the output directory).
+    pub template: String,
+    /// The commit id the template was read from, if it is a git repository at a ref.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+}
+
+impl Answers {
+    /// Loads the answers from `file`.
+    pub fn load(file: &Path) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(file)
+            .map_err(|_| Error::ReadFileFailed(file.display().to_string()))?;
+        serde_json::from_str::<Self>(content.as_str())
+            .map_err(|e| Error::AnswersIsNotValid(e.to_string()))
+    }
+
+    /// Returns the template paths resolved against `base_dir` (the directory of the answers file).
+    ///
+    /// The git repositories are pinned to the recorded commits.
+    pub fn template_paths(&self, base_dir: &Path) -> Vec<PathBuf> {
+        self.templates
+            .iter()
+            .map(|answered| {
+                let template = match (split_ref(answered.template.as_str()), &answered.commit) {
+                    (Some((repository, _)), Some(commit)) => format!("{}#{}", repository, commit),
+                    _ => answered.template.clone(),
+                };
+                base_dir.join(template)
+            })
+            .collect()
+    }
+
+    /// Moves the git repositories to `rev` (or to the latest commits of the recorded refs if `None`).
+    ///
+    /// The commits are cleared. Fails if a template is not a git repository at a ref.
+    pub fn retarget(&mut self, rev: Option<&str>) -> Result<(), Error> {
+        for answered in self.templates.iter_mut() {
+            let template = match (split_ref(answered.template.as_str()), &answered.commit) {
+                (Some((repository, old_rev)), Some(_)) => {
+                    format!("{}#{}", repository, rev.unwrap_or(old_rev))
+                }
+                _ => return Err(Error::TemplateIsNotVersioned(answered.template.clone())),
+            };
+            answered.template = template;
+            answered.commit = None;
+        }
+        Ok(())
+    }
+
+    /// Returns the answers as the `.tempura-answers.json` file to write with the rendered files.
+    pub fn to_output_file(&self) -> Result<OutputFile, Error> {
+        let mut content =
+            serde_json::to_vec_pretty(self).map_err(|e| Error::AnswersIsNotValid(e.to_string()))?;
+        content.push(b'\n');
+        Ok(OutputFile::new(ANSWERS_FILE_NAME, content))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_paths() {
+        let answers = Answers {
+            tempura: "0.1.6".to_string(),
+            templates: vec![
+                AnsweredTemplate {
+                    template: "../tmpl".to_string(),
+                    commit: None,
+                },
+                AnsweredTemplate {
+                    template: "/repo#v1".to_string(),
+                    commit: Some("0123abc".to_string()),
+                },
+            ],
+            delimiters: None,
+            include_vcs: false,
+            data: Data::new(),
+        };
+        assert_eq!(
+            answers.template_paths(Path::new("out")),
+            vec![PathBuf::from("out/../tmpl"), PathBuf::from("/repo#0123abc")]
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use std::{
+    collections::BTreeMap,
+    io::{Cursor, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    source::{Entry, MemorySource, Source},
+    Error, Output, OutputFile,
+};
+
+/// The mode of the files without the permission bits.
+const DEFAULT_MODE: u32 = 0o644;
+
+/// The archive formats.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Detects the archive format from the file name of `path`.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+        if file_name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if file_name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+impl std::str::FromStr for ArchiveFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tar" => Ok(Self::Tar),
+            "tar.gz" | "tgz" => Ok(Self::TarGz),
+            "zip" => Ok(Self::Zip),
+            _ => Err(Error::UnknownArchiveFormat(s.to_string())),
+        }
+    }
+}
+
+/// Reads the template files from a `.tar`, `.tar.gz` (`.tgz`) or `.zip` archive.
+///
+/// The archive root is the template root.
+#[derive(Debug)]
+pub struct ArchiveSource {
+    files: MemorySource,
+    modes: BTreeMap<PathBuf, u32>,
+}
+
+impl ArchiveSource {
+    pub fn open(file: &Path) -> Result<Self, Error> {
+        let format = ArchiveFormat::from_path(file).ok_or(Error::TemplateIsNotDirectory)?;
+        let reader = std::fs::File::open(file).map_err(|_| Error::TemplateNotFound)?;
+        let invalid = |e: &dyn std::fmt::Display| {
+            Error::ArchiveIsNotValid(format!("{}: {}", file.display(), e))
+        };
+        let mut files = MemorySource::default();
+        let mut modes = BTreeMap::new();
+        match format {
+            ArchiveFormat::Tar => {
+                read_tar(reader, &mut files, &mut modes).map_err(|e| invalid(&e))?
+            }
+            ArchiveFormat::TarGz => {
+                read_tar(flate2::read::GzDecoder::new(reader), &mut files, &mut modes)
+                    .map_err(|e| invalid(&e))?
+            }
+            ArchiveFormat::Zip => {
+                let mut zip = zip::ZipArchive::new(reader).map_err(|e| invalid(&e))?;
+                for i in 0..zip.len() {
+                    let mut entry = zip.by_index(i).map_err(|e| invalid(&e))?;
+                    if !entry.is_file() {
+                        continue;
+                    }
+                    let path = entry
+                        .enclosed_name()
+                        .and_then(|path| normalize(path.as_path()))
+                        .ok_or_else(|| invalid(&entry.name()))?;
+                    let mut content = vec![];
+                    entry.read_to_end(&mut content).map_err(|e| invalid(&e))?;
+                    if let Some(mode) = entry.unix_mode() {
+                        modes.insert(path.clone(), mode & 0o7777);
+                    }
+                    files.files.insert(path, content);
+                }
+            }
+        }
+        Ok(Self { files, modes })
+    }
+}
+
+impl Source for ArchiveSource {
+    fn read_dir(&self, dir: &Path) -> Result<Vec<Entry>, Error> {
+        self.files.read_dir(dir)
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Option<Vec<u8>>, Error> {
+        self.files.read_file(path)
+    }
+
+    fn mode(&self, path: &Path) -> Option<u32> {
+        self.modes.get(path).copied()
+    }
+}
+
+/// Writes the files to a tar, tar.gz or zip archive.
+///
+/// The archive is complete only after [`ArchiveOutput::finish`] is called.
+pub struct ArchiveOutput<W: Write> {
+    writer: ArchiveWriter<W>,
+}
+
+enum ArchiveWriter<W: Write> {
+    Tar(tar::Builder<W>),
+    TarGz(tar::Builder<flate2::write::GzEncoder<W>>),
+    // a zip archive needs `Seek`, so it is written to `W` on finish
+    Zip(Box<zip::ZipWriter<Cursor<Vec<u8>>>>, W),
+}
+
+impl<W: Write> std::fmt::Debug for ArchiveOutput<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArchiveOutput").finish_non_exhaustive()
+    }
+}
+
+impl<W: Write> ArchiveOutput<W> {
+    pub fn new(writer: W, format: ArchiveFormat) -> Self {
+        let writer = match format {
+            ArchiveFormat::Tar => ArchiveWriter::Tar(tar::Builder::new(writer)),
+            ArchiveFormat::TarGz => ArchiveWriter::TarGz(tar::Builder::new(
+                flate2::write::GzEncoder::new(writer, flate2::Compression::default()),
+            )),
+            ArchiveFormat::Zip => {
+                ArchiveWriter::Zip(Box::new(zip::ZipWriter::new(Cursor::new(vec![]))), writer)
+            }
+        };
+        Self { writer }
+    }
+
+    /// Completes the archive and returns the writer.
+    pub fn finish(self) -> Result<W, Error> {
+        let failed = |e: &dyn std::fmt::Display| Error::WriteFileFailed(e.to_string());
+        match self.writer {
+            ArchiveWriter::Tar(builder) => builder.into_inner().map_err(|e| failed(&e)),
+            ArchiveWriter::TarGz(builder) => builder
+                .into_inner()
+                .and_then(|encoder| encoder.finish())
+                .map_err(|e| failed(&e)),
+            ArchiveWriter::Zip(zip, mut writer) => {
+                let content = zip.finish().map_err(|e| failed(&e))?.into_inner();
+                writer
+                    .write_all(content.as_slice())
+                    .and_then(|_| writer.flush())
+                    .map_err(|e| failed(&e))?;
+                Ok(writer)
+            }
+        }
+    }
+}
+
+impl<W: Write> Output for ArchiveOutput<W> {
+    fn write_file(&mut self, output_file: &OutputFile) -> Result<(), Error> {
+        let path = crate::ignore::to_slash(output_file.path.as_path());
+        let mode = output_file.mode.unwrap_or(DEFAULT_MODE);
+        let content = output_file.content.as_slice();
+        let failed = |_| Error::WriteFileFailed(path.clone());
+        match &mut self.writer {
+            ArchiveWriter::Tar(builder) => {
+                append_tar(builder, &path, mode, content).map_err(failed)
+            }
+            ArchiveWriter::TarGz(builder) => {
+                append_tar(builder, &path, mode, content).map_err(failed)
+            }
+            ArchiveWriter::Zip(zip, _) => {
+                let options = zip::write::SimpleFileOptions::default().unix_permissions(mode);
+                zip.start_file(path.as_str(), options)
+                    .map_err(std::io::Error::from)
+                    .and_then(|_| zip.write_all(content))
+                    .map_err(failed)
+            }
+        }
+    }
+}
+
+fn append_tar<W: Write>(
+    builder: &mut tar::Builder<W>,
+    path: &str,
+    mode: u32,
+    content: &[u8],
+) -> std::io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(mode);
+    header.set_mtime(0);
+    builder.append_data(&mut header, path, content)
+}
+
+fn read_tar<R: Read>(
+    reader: R,
+    files: &mut MemorySource,
+    modes: &mut BTreeMap<PathBuf, u32>,
+) -> std::io::Result<()> {
+    let mut tar = tar::Archive::new(reader);
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?;
+        let path = normalize(path.as_ref()).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("invalid path: {}", path.display()),
+            )
+        })?;
+        let mut content = vec![];
+        entry.read_to_end(&mut content)?;
+        modes.insert(path.clone(), entry.header().mode()? & 0o7777);
+        files.files.insert(path, content);
+    }
+    Ok(())
+}
+
+/// Removes `.` from `path`. Returns `None` if `path` is empty, absolute or contains `..`.
+pub(crate) fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::Normal(name) => normalized.push(name),
+            _ => return None,
+        }
+    }
+    (!normalized.as_os_str().is_empty()).then_some(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_format_from_path() {
+        let f = |p: &str| ArchiveFormat::from_path(Path::new(p));
+        assert_eq!(f("tmpl.tar"), Some(ArchiveFormat::Tar));
+        assert_eq!(f("tmpl.tar.gz"), Some(ArchiveFormat::TarGz));
+        assert_eq!(f("tmpl.tgz"), Some(ArchiveFormat::TarGz));
+        assert_eq!(f("tmpl.zip"), Some(ArchiveFormat::Zip));
+        assert_eq!(f("tmpl"), None);
+        assert_eq!(f("tmpl.gz"), None);
+    }
+
+    #[test]
+    fn test_archive_output() -> Result<(), Error> {
+        for format in [ArchiveFormat::Tar, ArchiveFormat::TarGz, ArchiveFormat::Zip] {
+            let mut output = ArchiveOutput::new(vec![], format);
+            output.write_file(&OutputFile {
+                mode: Some(0o755),
+                ..OutputFile::new("a/b.sh", "#!/bin/sh")
+            })?;
+            output.write_file(&OutputFile::new("c.bin", vec![0xff, 0x00]))?;
+            let archive = std::env::temp_dir().join(format!(
+                "tempura-test-archive-output-{}.{}",
+                std::process::id(),
+                match format {
+                    ArchiveFormat::Tar => "tar",
+                    ArchiveFormat::TarGz => "tar.gz",
+                    ArchiveFormat::Zip => "zip",
+                }
+            ));
+            std::fs::write(archive.as_path(), output.finish()?).unwrap();
+            let source = ArchiveSource::open(archive.as_path());
+            std::fs::remove_file(archive.as_path()).unwrap();
+            let source = source?;
+            assert_eq!(
+                source.read_file(Path::new("a/b.sh"))?,
+                Some(b"#!/bin/sh".to_vec())
+            );
+            assert_eq!(source.mode(Path::new("a/b.sh")), Some(0o755));
+            assert_eq!(
+                source.read_file(Path::new("c.bin"))?,
+                Some(vec![0xff, 0x00])
+            );
+            assert_eq!(source.mode(Path::new("c.bin")), Some(DEFAULT_MODE));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive_format_from_str() {
+        assert_eq!(
+            "tar".parse::<ArchiveFormat>().ok(),
+            Some(ArchiveFormat::Tar)
+        );
+        assert_eq!(
+            "tgz".parse::<ArchiveFormat>().ok(),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(
+            "zip".parse::<ArchiveFormat>().ok(),
+            Some(ArchiveFormat::Zip)
+        );
+        assert!("rar".parse::<ArchiveFormat>().is_err());
+    }
+
+    #[test]
+    fn test_normalize() {
+        let f = |p: &str| normalize(Path::new(p));
+        assert_eq!(f("a/b"), Some(PathBuf::from("a/b")));
+        assert_eq!(f("./a/./b"), Some(PathBuf::from("a/b")));
+        assert_eq!(f("./"), None);
+        assert_eq!(f("/a"), None);
+        assert_eq!(f("a/../b"), None);
+    }
+}
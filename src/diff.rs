@@ -0,0 +1,430 @@
+/// Splits `s` into lines keeping the line endings.
+pub(crate) fn lines(s: &str) -> Vec<&str> {
+    s.split_inclusive('\n').collect()
+}
+
+/// Returns the pairs of the indices of the matching lines of `a` and `b` (a longest common subsequence).
+///
+/// This is the linear space variant of the Myers' O(ND) difference algorithm.
+pub(crate) fn matches(a: &[&str], b: &[&str]) -> Vec<(usize, usize)> {
+    let mut pairs = vec![];
+    let mut forward = V::new(a.len() + b.len());
+    let mut backward = V::new(a.len() + b.len());
+    conquer(a, 0, b, 0, &mut forward, &mut backward, &mut pairs);
+    pairs
+}
+
+/// The furthest reaching x on each diagonal k (from `-max` to `max`).
+struct V {
+    offset: isize,
+    v: Vec<usize>,
+}
+
+impl V {
+    fn new(max: usize) -> Self {
+        Self {
+            offset: max as isize + 1,
+            v: vec![0; 2 * max + 3],
+        }
+    }
+}
+
+impl std::ops::Index<isize> for V {
+    type Output = usize;
+
+    fn index(&self, k: isize) -> &usize {
+        &self.v[(k + self.offset) as usize]
+    }
+}
+
+impl std::ops::IndexMut<isize> for V {
+    fn index_mut(&mut self, k: isize) -> &mut usize {
+        &mut self.v[(k + self.offset) as usize]
+    }
+}
+
+/// Pushes the matching lines of `a` and `b` (starting at `a_start` and `b_start` in the whole
+/// sequences) to `pairs` by splitting them at a middle snake.
+fn conquer(
+    a: &[&str],
+    a_start: usize,
+    b: &[&str],
+    b_start: usize,
+    forward: &mut V,
+    backward: &mut V,
+    pairs: &mut Vec<(usize, usize)>,
+) {
+    let prefix = common_prefix(a, b);
+    pairs.extend((0..prefix).map(|i| (a_start + i, b_start + i)));
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    let (a_start, b_start) = (a_start + prefix, b_start + prefix);
+    let suffix = common_suffix(a, b);
+    let (a, b) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+    if !a.is_empty() && !b.is_empty() {
+        let (x, y) = middle_snake(a, b, forward, backward);
+        conquer(&a[..x], a_start, &b[..y], b_start, forward, backward, pairs);
+        conquer(
+            &a[x..],
+            a_start + x,
+            &b[y..],
+            b_start + y,
+            forward,
+            backward,
+            pairs,
+        );
+    }
+    pairs.extend((0..suffix).map(|i| (a_start + a.len() + i, b_start + b.len() + i)));
+}
+
+/// Returns the start of the middle snake of the shortest edit script from `a` to `b` (which have
+/// no common prefix or suffix and are not empty).
+fn middle_snake(a: &[&str], b: &[&str], forward: &mut V, backward: &mut V) -> (usize, usize) {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    forward[1] = 0;
+    backward[1] = 0;
+    for d in 0..=(n + m + 1) / 2 {
+        for k in (-d..=d).step_by(2) {
+            let x = if k == -d || (k != d && forward[k - 1] < forward[k + 1]) {
+                forward[k + 1]
+            } else {
+                forward[k - 1] + 1
+            };
+            let y = (x as isize - k) as usize;
+            let snake = if x < a.len() && y < b.len() {
+                common_prefix(&a[x..], &b[y..])
+            } else {
+                0
+            };
+            forward[k] = x + snake;
+            // the backward path on the diagonal is `-(k - delta)` from the end
+            if odd && (k - delta).abs() < d && forward[k] + backward[-(k - delta)] >= a.len() {
+                return (x, y);
+            }
+        }
+        for k in (-d..=d).step_by(2) {
+            let x = if k == -d || (k != d && backward[k - 1] < backward[k + 1]) {
+                backward[k + 1]
+            } else {
+                backward[k - 1] + 1
+            };
+            let y = (x as isize - k) as usize;
+            let snake = if x < a.len() && y < b.len() {
+                common_suffix(&a[..a.len() - x], &b[..b.len() - y])
+            } else {
+                0
+            };
+            backward[k] = x + snake;
+            if !odd && (k - delta).abs() <= d && backward[k] + forward[-(k - delta)] >= a.len() {
+                return (a.len() - backward[k], b.len() - (y + snake));
+            }
+        }
+    }
+    unreachable!("the middle snake is found within (n + m + 1) / 2 steps")
+}
+
+fn common_prefix(a: &[&str], b: &[&str]) -> usize {
+    a.iter().zip(b.iter()).take_while(|(a, b)| a == b).count()
+}
+
+fn common_suffix(a: &[&str], b: &[&str]) -> usize {
+    a.iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+/// The number of the context lines around the changes in a unified diff.
+const CONTEXT: usize = 3;
+
+/// Returns the unified diff from `old` to `new` (only the headers if they are the same).
+pub(crate) fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let (old, new) = (lines(old), lines(new));
+    // the edit script as (old index, new index) with `None` for insertions and deletions
+    let mut ops = vec![];
+    let (mut i, mut j) = (0, 0);
+    for (next_i, next_j) in matches(old.as_slice(), new.as_slice())
+        .into_iter()
+        .chain(std::iter::once((old.len(), new.len())))
+    {
+        ops.extend((i..next_i).map(|i| (Some(i), None)));
+        ops.extend((j..next_j).map(|j| (None, Some(j))));
+        if next_i < old.len() {
+            ops.push((Some(next_i), Some(next_j)));
+        }
+        (i, j) = (next_i + 1, next_j + 1);
+    }
+
+    let mut diff = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let is_change = |op: &(Option<usize>, Option<usize>)| op.0.is_none() || op.1.is_none();
+    let mut start = 0;
+    while let Some(first) = ops[start..].iter().position(is_change).map(|k| start + k) {
+        // extend the hunk while the next change is within the context
+        let mut last = first;
+        while let Some(next) = ops[last + 1..]
+            .iter()
+            .position(is_change)
+            .map(|k| last + 1 + k)
+        {
+            if next - last > 2 * CONTEXT {
+                break;
+            }
+            last = next;
+        }
+        let hunk =
+            &ops[first.saturating_sub(CONTEXT).max(start)..(last + 1 + CONTEXT).min(ops.len())];
+        let range = |indices: Vec<usize>, before: usize| match indices.as_slice() {
+            [] => format!("{},0", before),
+            [first] => format!("{}", first + 1),
+            [first, ..] => format!("{},{}", first + 1, indices.len()),
+        };
+        let old_before = hunk.iter().find_map(|op| op.0).unwrap_or(old.len());
+        let new_before = hunk.iter().find_map(|op| op.1).unwrap_or(new.len());
+        diff.push_str(
+            format!(
+                "@@ -{} +{} @@\n",
+                range(hunk.iter().filter_map(|op| op.0).collect(), old_before),
+                range(hunk.iter().filter_map(|op| op.1).collect(), new_before)
+            )
+            .as_str(),
+        );
+        for op in hunk {
+            let (prefix, line) = match op {
+                (Some(i), Some(_)) => (' ', old[*i]),
+                (Some(i), None) => ('-', old[*i]),
+                (None, Some(j)) => ('+', new[*j]),
+                (None, None) => unreachable!(),
+            };
+            diff.push(prefix);
+            diff.push_str(line);
+            if !line.ends_with('\n') {
+                diff.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+        start = (last + 1 + CONTEXT).min(ops.len());
+    }
+    diff
+}
+
+/// The result of a three-way merge.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Merged {
+    pub(crate) content: String,
+    pub(crate) conflicts: usize,
+}
+
+/// Merges the changes from `base` to `ours` and from `base` to `theirs` line by line.
+///
+/// The conflicting changes are written with the conflict markers.
+pub(crate) fn merge3(base: &str, ours: &str, theirs: &str) -> Merged {
+    let (base, ours, theirs) = (lines(base), lines(ours), lines(theirs));
+    let to_map = |other: &[&str]| {
+        let mut map = vec![None; base.len()];
+        for (i, j) in matches(base.as_slice(), other) {
+            map[i] = Some(j);
+        }
+        map
+    };
+    let (ours_map, theirs_map) = (to_map(ours.as_slice()), to_map(theirs.as_slice()));
+
+    let mut merged = Merged {
+        content: String::new(),
+        conflicts: 0,
+    };
+    let (mut o, mut a, mut b) = (0, 0, 0);
+    loop {
+        // the stable lines (unchanged in both)
+        let mut i = 0;
+        while o + i < base.len()
+            && ours_map[o + i] == Some(a + i)
+            && theirs_map[o + i] == Some(b + i)
+        {
+            i += 1;
+        }
+        if i > 0 {
+            merged.content.extend(base[o..o + i].iter().copied());
+            (o, a, b) = (o + i, a + i, b + i);
+            continue;
+        }
+        // the unstable lines (changed in either) up to the next line matched in both
+        match (o..base.len()).find(|&i| ours_map[i].is_some() && theirs_map[i].is_some()) {
+            Some(next) => {
+                let (next_a, next_b) = (ours_map[next].unwrap(), theirs_map[next].unwrap());
+                merge_chunk(
+                    &base[o..next],
+                    &ours[a..next_a],
+                    &theirs[b..next_b],
+                    &mut merged,
+                );
+                (o, a, b) = (next, next_a, next_b);
+            }
+            None => {
+                merge_chunk(&base[o..], &ours[a..], &theirs[b..], &mut merged);
+                break;
+            }
+        }
+    }
+    merged
+}
+
+fn merge_chunk(base: &[&str], ours: &[&str], theirs: &[&str], merged: &mut Merged) {
+    let chunk = if ours == base {
+        theirs
+    } else if theirs == base || ours == theirs {
+        ours
+    } else {
+        merged.conflicts += 1;
+        let mut push_lines = |marker: &str, lines: &[&str]| {
+            merged.content.push_str(marker);
+            for line in lines {
+                merged.content.push_str(line);
+            }
+            if !merged.content.ends_with('\n') {
+                merged.content.push('\n');
+            }
+        };
+        push_lines("<<<<<<< local\n", ours);
+        push_lines("=======\n", theirs);
+        merged.content.push_str(">>>>>>> template\n");
+        return;
+    };
+    merged.content.extend(chunk.iter().copied());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let f = |a: &str, b: &str| {
+            let (a, b) = (lines(a), lines(b));
+            matches(a.as_slice(), b.as_slice())
+        };
+        assert_eq!(f("", ""), vec![]);
+        assert_eq!(f("a\n", ""), vec![]);
+        assert_eq!(f("a\nb\nc\n", "a\nb\nc\n"), vec![(0, 0), (1, 1), (2, 2)]);
+        assert_eq!(f("a\nb\nc\n", "a\nc\n"), vec![(0, 0), (2, 1)]);
+        assert_eq!(f("a\nc\n", "x\na\nb\nc\n"), vec![(0, 1), (1, 3)]);
+        assert_eq!(f("a\nb\nc\na\nb\nb\na\n", "c\nb\na\nb\na\nc\n").len(), 4);
+        assert_eq!(f("a\n", "b\n"), vec![]);
+        assert_eq!(f("a\nb\n", "b\na\n").len(), 1);
+
+        // the matches are a longest common subsequence
+        let lcs = |a: &[&str], b: &[&str]| {
+            let mut t = vec![vec![0; b.len() + 1]; a.len() + 1];
+            for i in (0..a.len()).rev() {
+                for j in (0..b.len()).rev() {
+                    t[i][j] = if a[i] == b[j] {
+                        t[i + 1][j + 1] + 1
+                    } else {
+                        t[i + 1][j].max(t[i][j + 1])
+                    };
+                }
+            }
+            t[0][0]
+        };
+        let mut seed = 1_u32;
+        let mut random = |len: usize| {
+            (0..len)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                    ["a\n", "b\n", "c\n"][(seed >> 16) as usize % 3]
+                })
+                .collect::<Vec<&str>>()
+        };
+        for len in 0..40 {
+            let (a, b) = (random(len), random(len / 2 + 3));
+            let pairs = matches(a.as_slice(), b.as_slice());
+            assert_eq!(pairs.len(), lcs(a.as_slice(), b.as_slice()));
+            assert!(pairs.iter().all(|(i, j)| a[*i] == b[*j]));
+            assert!(pairs.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+        }
+
+        // a fully rewritten large file is diffed in linear space
+        let a = (0..2000).map(|_| "a\n").collect::<Vec<&str>>();
+        let b = (0..2000).map(|_| "b\n").collect::<Vec<&str>>();
+        assert_eq!(matches(a.as_slice(), b.as_slice()), vec![]);
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let f = |old: &str, new: &str| unified_diff(old, new, "a/f", "b/f");
+        assert_eq!(f("a\n", "a\n"), "--- a/f\n+++ b/f\n");
+        assert_eq!(
+            f("", "a\nb\n"),
+            "--- a/f\n+++ b/f\n@@ -0,0 +1,2 @@\n+a\n+b\n"
+        );
+        assert_eq!(f("a\n", ""), "--- a/f\n+++ b/f\n@@ -1 +0,0 @@\n-a\n");
+        assert_eq!(
+            f("1\n2\n3\n4\n5\n", "1\n2\nx\n4\n5\n"),
+            "--- a/f\n+++ b/f\n@@ -1,5 +1,5 @@\n 1\n 2\n-3\n+x\n 4\n 5\n"
+        );
+        let old = (1..=20).map(|i| format!("{}\n", i)).collect::<String>();
+        let new = (1..=20)
+            .map(|i| match i {
+                2 => "two\n".to_string(),
+                19 => "nineteen\n".to_string(),
+                _ => format!("{}\n", i),
+            })
+            .collect::<String>();
+        assert_eq!(
+            f(old.as_str(), new.as_str()),
+            [
+                "--- a/f",
+                "+++ b/f",
+                "@@ -1,5 +1,5 @@",
+                " 1",
+                "-2",
+                "+two",
+                " 3",
+                " 4",
+                " 5",
+                "@@ -16,5 +16,5 @@",
+                " 16",
+                " 17",
+                " 18",
+                "-19",
+                "+nineteen",
+                " 20",
+                "",
+            ]
+            .join("\n")
+        );
+        assert_eq!(
+            f("a", "b"),
+            "--- a/f\n+++ b/f\n@@ -1 +1 @@\n-a\n\\ No newline at end of file\n+b\n\\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn test_merge3() {
+        let f = |base: &str, ours: &str, theirs: &str| merge3(base, ours, theirs);
+        let clean = |content: &str| Merged {
+            content: content.to_string(),
+            conflicts: 0,
+        };
+        assert_eq!(f("a\nb\nc\n", "a\nb\nc\n", "a\nB\nc\n"), clean("a\nB\nc\n"));
+        assert_eq!(f("a\nb\nc\n", "A\nb\nc\n", "a\nb\nc\n"), clean("A\nb\nc\n"));
+        assert_eq!(f("a\nb\nc\n", "A\nb\nc\n", "a\nb\nC\n"), clean("A\nb\nC\n"));
+        assert_eq!(f("a\nb\n", "x\na\nb\n", "a\nb\ny\n"), clean("x\na\nb\ny\n"));
+        assert_eq!(f("a\nb\nc\n", "a\nc\n", "a\nc\n"), clean("a\nc\n"));
+        assert_eq!(f("", "a\n", "a\n"), clean("a\n"));
+        assert_eq!(
+            f("a\nb\nc\n", "a\nB\nc\n", "a\nX\nc\n"),
+            Merged {
+                content: "a\n<<<<<<< local\nB\n=======\nX\n>>>>>>> template\nc\n".to_string(),
+                conflicts: 1,
+            }
+        );
+        assert_eq!(
+            f("a", "b", "c"),
+            Merged {
+                content: "<<<<<<< local\nb\n=======\nc\n>>>>>>> template\n".to_string(),
+                conflicts: 1,
+            }
+        );
+    }
+}
@@ -0,0 +1,588 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    archive::{normalize, ArchiveFormat, ArchiveSource},
+    diff::unified_diff,
+    front_matter::{output_path, strip_front_matter},
+    git::{split_ref, GitSource},
+    ignore::{to_slash, Ignore, IGNORE_FILE_NAME},
+    inject::apply_injection,
+    manifest::{Manifest, MANIFEST_FILE_NAME},
+    output::apply_to_existing,
+    source::{DirSource, Entry, Source},
+    template::{split_file_blocks, strip_delimiters_comment, Partials},
+    ConflictPolicy, Data, Delimiters, Error, Injection, MergeMode, Output, Template,
+};
+
+const RAW_FILE_EXTENSION: &str = ".raw";
+
+/// The template directories to render.
+///
+/// ```no_run
+/// # fn main() -> Result<(), tempura::Error> {
+/// let template_dir = tempura::TemplateDir::open(&["base", "overlay"], None)?;
+/// let data = [("name".to_string(), "World".to_string())].into_iter().collect();
+/// let rendered = template_dir.render(&data)?;
+/// for output_file in &rendered.files {
+///     println!("{}", output_file.path.display());
+/// }
+/// rendered.write(&mut tempura::FsOutput::new(std::path::Path::new("out")))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TemplateDir {
+    layers: Vec<Layer>,
+    commits: Vec<Option<String>>,
+    include_vcs: bool,
+}
+
+impl TemplateDir {
+    /// Opens the template directories.
+    ///
+    /// `delimiters` overrides the delimiters in the manifests.
+    pub fn open<P: AsRef<Path>>(
+        templates: &[P],
+        delimiters: Option<&Delimiters>,
+    ) -> Result<Self, Error> {
+        let sources = templates
+            .iter()
+            .map(|template| open_source(template.as_ref()))
+            .collect::<Result<Vec<Box<dyn Source>>, Error>>()?;
+        Self::from_sources(sources, delimiters)
+    }
+
+    /// Opens the templates read from `sources`.
+    ///
+    /// `delimiters` overrides the delimiters in the manifests.
+    pub fn from_sources(
+        sources: Vec<Box<dyn Source>>,
+        delimiters: Option<&Delimiters>,
+    ) -> Result<Self, Error> {
+        let commits = sources
+            .iter()
+            .map(|source| source.commit().map(str::to_string))
+            .collect();
+        let mut layers = vec![];
+        for source in sources {
+            load_layers(source, delimiters, &mut layers, &mut vec![])?;
+        }
+        Ok(Self {
+            layers,
+            commits,
+            include_vcs: false,
+        })
+    }
+
+    /// Returns the commit ids of the sources read from git repositories (in the order of the sources).
+    pub fn commits(&self) -> &[Option<String>] {
+        self.commits.as_slice()
+    }
+
+    /// Sets whether the version control metadata (`.git`, `.hg`, `.svn`, etc.) is rendered.
+    /// It is skipped by default.
+    pub fn include_vcs(mut self, include_vcs: bool) -> Self {
+        self.include_vcs = include_vcs;
+        self
+    }
+
+    /// Renders the template directories with `data` in memory.
+    pub fn render(&self, data: &Data) -> Result<Rendered, Error> {
+        let partials = Partials {
+            dirs: self
+                .layers
+                .iter()
+                .map(|layer| {
+                    (
+                        layer.source.as_ref(),
+                        layer.manifest.partials_dir().to_path_buf(),
+                    )
+                })
+                .collect(),
+        };
+        let ignore = if self.include_vcs {
+            Ignore::default()
+        } else {
+            Ignore::vcs()
+        };
+        let mut template_files = BTreeMap::new();
+        for layer in self.layers.iter() {
+            handle_directory(Path::new(""), layer, &ignore, &mut template_files)?;
+        }
+        let mut rendered = Rendered::default();
+        for (template_file, layer) in template_files.iter() {
+            let context = Context {
+                source: layer.source.as_ref(),
+                data,
+                delimiters: &layer.delimiters,
+                manifest: &layer.manifest,
+                partials: &partials,
+            };
+            let (output_files, diagnostics) = handle_file(template_file, &context)?;
+            rendered.files.extend(output_files);
+            for diagnostic in diagnostics {
+                if !rendered.diagnostics.contains(&diagnostic) {
+                    rendered.diagnostics.push(diagnostic);
+                }
+            }
+        }
+        Ok(rendered)
+    }
+}
+
+/// The rendered files.
+#[derive(Debug, Default)]
+pub struct Rendered {
+    pub files: Vec<OutputFile>,
+    /// The malformed tags that look like intended tags (with their locations).
+    pub diagnostics: Vec<String>,
+}
+
+impl Rendered {
+    /// Writes the rendered files to `output`.
+    pub fn write(&self, output: &mut dyn Output) -> Result<(), Error> {
+        for output_file in self.files.iter() {
+            output.write_file(output_file)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the unified diff from the files in `output_dir` to the rendered files (empty if they
+    /// are up to date). The files in `output_dir` that are not rendered are not compared.
+    pub fn diff(&self, output_dir: &Path) -> Result<String, Error> {
+        let mut diff = String::new();
+        for output_file in self.files.iter() {
+            let path = to_slash(output_file.path.as_path());
+            let file = output_dir.join(output_file.path.as_path());
+            let (old_name, old, new) = if file.is_file() {
+                let content = std::fs::read(file.as_path())
+                    .map_err(|_| Error::ReadFileFailed(file.display().to_string()))?;
+                let new = match apply_to_existing(content.as_slice(), output_file)? {
+                    Some(new) => new,
+                    None if output_file.conflict == ConflictPolicy::Skip => continue,
+                    None => output_file.content.clone(),
+                };
+                if content == new {
+                    continue;
+                }
+                (format!("a/{}", path), content, new)
+            } else {
+                // a missing file is shown as new even if it has no anchor to inject into (as `check`
+                // reports it as missing)
+                let new = match apply_injection(&[], output_file) {
+                    Ok(Some(new)) => new,
+                    Ok(None) | Err(Error::AnchorNotFound(_, _)) => output_file.content.clone(),
+                    Err(e) => return Err(e),
+                };
+                ("/dev/null".to_string(), vec![], new)
+            };
+            let new_name = format!("b/{}", path);
+            match (
+                std::str::from_utf8(old.as_slice()),
+                std::str::from_utf8(new.as_slice()),
+            ) {
+                (Ok(old), Ok(new)) => diff.push_str(
+                    unified_diff(old, new, old_name.as_str(), new_name.as_str()).as_str(),
+                ),
+                _ => diff.push_str(
+                    format!("Binary files {} and {} differ\n", old_name, new_name).as_str(),
+                ),
+            }
+        }
+        Ok(diff)
+    }
+
+    /// Returns the rendered files that are missing in `output_dir` or differ from the files in it.
+    pub fn check(&self, output_dir: &Path) -> Result<Vec<(PathBuf, CheckStatus)>, Error> {
+        let mut statuses = vec![];
+        for output_file in self.files.iter() {
+            let file = output_dir.join(output_file.path.as_path());
+            if !file.is_file() {
+                statuses.push((output_file.path.clone(), CheckStatus::Missing));
+                continue;
+            }
+            let content = std::fs::read(file.as_path())
+                .map_err(|_| Error::ReadFileFailed(file.display().to_string()))?;
+            let status = match apply_to_existing(content.as_slice(), output_file)? {
+                Some(new) if new != content => CheckStatus::Stale,
+                None if output_file.conflict != ConflictPolicy::Skip
+                    && content != output_file.content =>
+                {
+                    CheckStatus::Stale
+                }
+                _ => continue,
+            };
+            statuses.push((output_file.path.clone(), status));
+        }
+        Ok(statuses)
+    }
+
+    /// Returns the rendered files as a map from the paths to the contents.
+    pub fn into_map(self) -> BTreeMap<PathBuf, Vec<u8>> {
+        self.files
+            .into_iter()
+            .map(|output_file| (output_file.path, output_file.content))
+            .collect()
+    }
+}
+
+/// Why a rendered file is out of date.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CheckStatus {
+    /// The file does not exist.
+    Missing,
+    /// The file differs from the rendered file.
+    Stale,
+}
+
+impl std::fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Missing => "missing",
+            Self::Stale => "stale",
+        })
+    }
+}
+
+/// A rendered file.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct OutputFile {
+    /// The path relative to the output directory.
+    pub path: PathBuf,
+    pub content: Vec<u8>,
+    /// The Unix permission bits of the template file, if known.
+    pub mode: Option<u32>,
+    /// How the file is injected into the existing file, if it is a snippet.
+    pub inject: Option<Injection>,
+    /// How the file is merged into the existing file, if it is a structured document.
+    pub merge: Option<MergeMode>,
+    /// What to do if the file already exists.
+    pub conflict: ConflictPolicy,
+}
+
+impl OutputFile {
+    /// Creates a rendered file without the mode, injection or merge mode and with the default
+    /// conflict policy.
+    pub fn new(path: impl Into<PathBuf>, content: impl Into<Vec<u8>>) -> Self {
+        Self {
+            path: path.into(),
+            content: content.into(),
+            mode: None,
+            inject: None,
+            merge: None,
+            conflict: ConflictPolicy::default(),
+        }
+    }
+}
+
+/// A template directory.
+///
+/// When multiple template directories are given, later layers add or replace the files of earlier ones.
+#[derive(Debug)]
+struct Layer {
+    source: Box<dyn Source>,
+    manifest: Manifest,
+    delimiters: Delimiters,
+}
+
+/// Opens `template` as an archive if it is an archive file, as a git repository at a ref if it is
+/// `<repository>#<ref>` (and does not exist as is), otherwise as a directory.
+fn open_source(template: &Path) -> Result<Box<dyn Source>, Error> {
+    if template.is_file() && ArchiveFormat::from_path(template).is_some() {
+        return Ok(Box::new(ArchiveSource::open(template)?));
+    }
+    if !template.exists() {
+        if let Some((repository, rev)) = template.to_str().and_then(split_ref) {
+            return Ok(Box::new(GitSource::open(Path::new(repository), rev)?));
+        }
+    }
+    Ok(Box::new(DirSource::open(template)?))
+}
+
+/// Loads the layer of `source` and its parents declared with `extends` in the manifest.
+fn load_layers(
+    source: Box<dyn Source>,
+    delimiters: Option<&Delimiters>,
+    layers: &mut Vec<Layer>,
+    extending: &mut Vec<PathBuf>,
+) -> Result<(), Error> {
+    if let Some(template_dir) = source.dir() {
+        if extending.iter().any(|it| it == template_dir) {
+            extending.push(template_dir.to_path_buf());
+            return Err(Error::ExtendsCycle(
+                extending
+                    .iter()
+                    .map(|it| it.display().to_string())
+                    .collect::<Vec<String>>()
+                    .join(" -> "),
+            ));
+        }
+        if layers
+            .iter()
+            .any(|layer| layer.source.dir() == Some(template_dir))
+        {
+            return Ok(());
+        }
+    }
+
+    let manifest = Manifest::load(source.as_ref())?;
+    if let Some(parent) = manifest.extends() {
+        let template_dir = source
+            .dir()
+            .ok_or_else(|| Error::ExtendsNotSupported(parent.display().to_string()))?;
+        extending.push(template_dir.to_path_buf());
+        load_layers(
+            open_source(template_dir.join(parent).as_path())?,
+            delimiters,
+            layers,
+            extending,
+        )?;
+        extending.pop();
+    }
+    let delimiters = match delimiters {
+        Some(delimiters) => delimiters.clone(),
+        None => manifest.delimiters()?.unwrap_or_default(),
+    };
+    layers.push(Layer {
+        source,
+        manifest,
+        delimiters,
+    });
+    Ok(())
+}
+
+struct Context<'a> {
+    source: &'a dyn Source,
+    data: &'a Data,
+    delimiters: &'a Delimiters,
+    manifest: &'a Manifest,
+    partials: &'a Partials<'a>,
+}
+
+/// Collects the template files in `dir` into `template_files` (keyed by the path relative to the layer).
+fn handle_directory<'a>(
+    dir: &Path,
+    layer: &'a Layer,
+    ignore: &Ignore,
+    template_files: &mut BTreeMap<PathBuf, &'a Layer>,
+) -> Result<(), Error> {
+    let ignore_file = dir.join(IGNORE_FILE_NAME);
+    let ignore_rules = layer
+        .source
+        .read_file(ignore_file.as_path())?
+        .map(|content| {
+            String::from_utf8(content)
+                .map_err(|_| Error::ReadFileFailed(ignore_file.display().to_string()))
+        })
+        .transpose()?;
+    let ignore = ignore.with_dir(dir, ignore_rules.as_deref());
+
+    for Entry { path, is_dir } in layer.source.read_dir(dir)? {
+        if path == Path::new(MANIFEST_FILE_NAME)
+            || path == layer.manifest.partials_dir()
+            || ignore.is_ignored(path.as_path(), is_dir)
+        {
+            continue;
+        }
+        if is_dir {
+            handle_directory(&path, layer, &ignore, template_files)?;
+        } else {
+            template_files.insert(path, layer);
+        }
+    }
+    Ok(())
+}
+
+/// Renders `file` into the output file and the files of its file blocks. Returns no files if the
+/// file is not emitted (by the `if` in the front matter).
+fn handle_file(file: &Path, context: &Context) -> Result<(Vec<OutputFile>, Vec<String>), Error> {
+    let Context {
+        source,
+        data,
+        delimiters,
+        manifest,
+        partials,
+    } = *context;
+    // println!("DEBUG: file = {:?}", file);
+
+    let dir = file.parent().expect("file to have parent");
+
+    let file_name = file.file_name().expect("file to have file_name");
+    let file_name = file_name
+        .to_str()
+        .ok_or_else(|| Error::TemplateFileNameIsNotUtf8(file_name.to_string_lossy().to_string()))?;
+    let (file_name_tmpl, is_raw) = match file_name.strip_suffix(RAW_FILE_EXTENSION) {
+        Some(stem) if !stem.is_empty() => (stem, true),
+        _ => (file_name, manifest.is_raw(dir.join(file_name).as_path())),
+    };
+
+    let file_content = source
+        .read_file(file)?
+        .ok_or_else(|| Error::ReadFileFailed(file_name.to_string()))?;
+    // binary (non-UTF-8) files have no front matter
+    let (front_matter, file_content) = match String::from_utf8(file_content) {
+        Err(e) => (None, Err(e.into_bytes())),
+        Ok(file_content) => match strip_front_matter(file, file_content.as_str())? {
+            Some((front_matter, rest)) => (Some(front_matter), Ok(rest.to_string())),
+            None => (None, Ok(file_content)),
+        },
+    };
+    let front_matter = front_matter.unwrap_or_default();
+    if !front_matter.is_emitted(data) {
+        return Ok((vec![], vec![]));
+    }
+    let content_delimiters = front_matter
+        .delimiters()?
+        .unwrap_or_else(|| delimiters.clone());
+
+    let mut diagnostics = vec![];
+    let mut parse = |location: String, tmpl: &str, delimiters: &Delimiters| {
+        let template = Template::parse_with_delimiters(tmpl, delimiters);
+        for diagnostic in template.diagnostics() {
+            diagnostics.push(format!("{}:{}", location, diagnostic));
+        }
+        template
+    };
+    let output_file_path = match front_matter.path.as_deref() {
+        Some(path) => {
+            let path = parse(
+                format!("{} (path)", dir.join(file_name).display()),
+                path,
+                &content_delimiters,
+            )
+            .render_with_partials(data, partials)?;
+            output_path(file, path.as_str())?
+        }
+        None => {
+            // only the path filter (e.g. `{{package|path}}`) expands into nested directories
+            let contains_path_separator =
+                |segments: &[String]| segments.iter().any(|s| s.contains(std::path::is_separator));
+            let output_file_name = parse(
+                format!("{} (name)", dir.join(file_name).display()),
+                file_name_tmpl,
+                delimiters,
+            )
+            .render_path_segments(data, partials)?;
+            if contains_path_separator(&output_file_name) {
+                return Err(Error::VariableContainsPathSeparator(
+                    dir.join(file_name).display().to_string(),
+                    dir.join(output_file_name.join("/")).display().to_string(),
+                ));
+            }
+            let output_file_parent_dir = {
+                let mut dirs = vec![];
+                let mut p = dir;
+                loop {
+                    let dir_name = match p.file_name() {
+                        None => break,
+                        Some(x) => x,
+                    };
+                    let dir_name = dir_name.to_str().ok_or_else(|| {
+                        Error::TemplateDirNameIsNotUtf8(dir.to_string_lossy().to_string())
+                    })?;
+                    let output_dir_name =
+                        parse(format!("{} (name)", p.display()), dir_name, delimiters)
+                            .render_path_segments(data, partials)?;
+                    if contains_path_separator(&output_dir_name) {
+                        return Err(Error::VariableContainsPathSeparator(
+                            dir.join(file_name).display().to_string(),
+                            dir.join(output_dir_name.join("/")).display().to_string(),
+                        ));
+                    }
+                    dirs.push(output_dir_name);
+                    match p.parent() {
+                        None => break,
+                        Some(x) => p = x,
+                    }
+                }
+                dirs.reverse();
+                let mut output_dir = PathBuf::new();
+                for d in dirs.into_iter().flatten() {
+                    output_dir = output_dir.join(d);
+                }
+                output_dir
+            };
+            output_file_name
+                .iter()
+                .fold(output_file_parent_dir, |path, segment| path.join(segment))
+        }
+    };
+
+    let mut blocks = vec![];
+    let output_file_content = match file_content {
+        // binary (non-UTF-8) files are copied as is
+        Err(file_content) => file_content,
+        Ok(file_content) if is_raw => file_content.into_bytes(),
+        Ok(file_content) => {
+            let (delimiters, file_content) = match strip_delimiters_comment(file_content.as_str())?
+            {
+                Some((delimiters, file_content)) => (delimiters, file_content),
+                None => (content_delimiters, file_content.as_str()),
+            };
+            let (file_content, file_blocks) = split_file_blocks(file_content, &delimiters)?;
+            for (path, content) in file_blocks {
+                let path = parse(
+                    format!("{} (file)", dir.join(file_name).display()),
+                    path,
+                    &delimiters,
+                )
+                .render_with_partials(data, partials)?;
+                let path = normalize(Path::new(path.as_str())).ok_or_else(|| {
+                    Error::FileBlockIsNotValid(format!(
+                        "{}: invalid path: {}",
+                        dir.join(file_name).display(),
+                        path
+                    ))
+                })?;
+                let content = parse(
+                    format!("{} ({})", dir.join(file_name).display(), path.display()),
+                    content,
+                    &delimiters,
+                )
+                .render_with_partials(data, partials)?;
+                blocks.push((path, content.into_bytes()));
+            }
+            parse(
+                dir.join(file_name).display().to_string(),
+                file_content.as_str(),
+                &delimiters,
+            )
+            .render_with_partials(data, partials)?
+            .into_bytes()
+        }
+    };
+
+    // println!("DEBUG: output_file_path = {:?}", output_file_path);
+    // println!("DEBUG: output_file_content = {:?}", output_file_content);
+
+    let mode = front_matter.mode.or_else(|| source.mode(file));
+    let conflict = front_matter.conflict.unwrap_or_default();
+    let mut output_files = vec![];
+    // the file with only file blocks is not emitted itself
+    if blocks.is_empty() || !output_file_content.trim_ascii().is_empty() {
+        output_files.push(OutputFile {
+            path: output_file_path,
+            content: output_file_content,
+            mode,
+            inject: front_matter
+                .inject
+                .or_else(|| manifest.injection(dir.join(file_name).as_path())),
+            merge: front_matter
+                .merge
+                .or_else(|| manifest.merge_mode(dir.join(file_name).as_path())),
+            conflict,
+        });
+    }
+    for (path, content) in blocks {
+        output_files.push(OutputFile {
+            mode,
+            conflict,
+            ..OutputFile::new(path, content)
+        });
+    }
+    Ok((output_files, diagnostics))
+}
@@ -0,0 +1,213 @@
+use std::path::{Path, PathBuf};
+
+use crate::{archive::normalize, ConflictPolicy, Data, Delimiters, Error, Injection, MergeMode};
+
+const OPEN: &str = "---tempura";
+const CLOSE: &str = "---";
+
+/// The per-file settings in the front matter block (`---tempura` ... `---`, written in TOML) at the
+/// beginning of a template file.
+#[derive(Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct FrontMatter {
+    /// The output path (a template relative to the output directory).
+    pub path: Option<String>,
+    /// The variable name (`!` to negate) that must be set to a value other than `""` and `"false"`
+    /// to emit the file.
+    #[serde(rename = "if")]
+    pub condition: Option<String>,
+    /// The Unix permission bits (e.g. `0o755` or the octal digits `"755"`).
+    #[serde(default, deserialize_with = "deserialize_mode")]
+    pub mode: Option<u32>,
+    /// The delimiters in the format `<open> <close>`.
+    pub delimiters: Option<String>,
+    pub conflict: Option<ConflictPolicy>,
+    pub inject: Option<Injection>,
+    pub merge: Option<MergeMode>,
+}
+
+impl FrontMatter {
+    /// Returns `true` if the file is emitted with `data`.
+    pub fn is_emitted(&self, data: &Data) -> bool {
+        let Some(condition) = self.condition.as_deref() else {
+            return true;
+        };
+        let (negated, name) = match condition.trim().strip_prefix('!') {
+            Some(name) => (true, name.trim()),
+            None => (false, condition.trim()),
+        };
+        let value = data
+            .get(name)
+            .is_some_and(|value| !value.is_empty() && value != "false");
+        value != negated
+    }
+
+    pub fn delimiters(&self) -> Result<Option<Delimiters>, Error> {
+        self.delimiters
+            .as_deref()
+            .map(str::parse::<Delimiters>)
+            .transpose()
+    }
+}
+
+fn deserialize_mode<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u32>, D::Error> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Mode {
+        Number(u32),
+        String(String),
+    }
+    // the integers are taken at face value (e.g. `0o755`) and the strings are octal digits
+    let (mode, value) = match <Option<Mode> as serde::Deserialize>::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(Mode::Number(number)) => (Some(number), format!("{:#o}", number)),
+        Some(Mode::String(string)) => (u32::from_str_radix(string.as_str(), 8).ok(), string),
+    };
+    mode.filter(|mode| *mode <= 0o7777)
+        .map(Some)
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid mode: {}", value)))
+}
+
+/// Removes the front matter block at the beginning of `content` (`file` is for the error messages).
+pub(crate) fn strip_front_matter<'a>(
+    file: &Path,
+    content: &'a str,
+) -> Result<Option<(FrontMatter, &'a str)>, Error> {
+    let mut lines = content.split_inclusive('\n');
+    if lines.next().map(str::trim_end) != Some(OPEN) {
+        return Ok(None);
+    }
+    let mut offset = content
+        .find('\n')
+        .map(|index| index + 1)
+        .unwrap_or(content.len());
+    let start = offset;
+    for line in lines {
+        if line.trim_end() == CLOSE {
+            let front_matter = if content[start..offset].trim().is_empty() {
+                FrontMatter::default()
+            } else {
+                toml_edit::de::from_str::<FrontMatter>(&content[start..offset])
+                    .map_err(|e| invalid(file, &e))?
+            };
+            return Ok(Some((front_matter, &content[offset + line.len()..])));
+        }
+        offset += line.len();
+    }
+    Err(invalid(file, &"front matter is not closed"))
+}
+
+/// Returns `path` (rendered) as a relative path. Returns an error if it is empty, absolute or
+/// contains `..`.
+pub(crate) fn output_path(file: &Path, path: &str) -> Result<PathBuf, Error> {
+    normalize(Path::new(path)).ok_or_else(|| invalid(file, &format!("invalid path: {}", path)))
+}
+
+fn invalid(file: &Path, e: &dyn std::fmt::Display) -> Error {
+    Error::FrontMatterIsNotValid(format!("{}: {}", file.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_front_matter() -> Result<(), Error> {
+        let file = Path::new("a.sh");
+        assert_eq!(strip_front_matter(file, "a\n")?, None);
+        assert_eq!(strip_front_matter(file, "x---tempura\n---\n")?, None);
+        assert_eq!(
+            strip_front_matter(file, "---tempura\n---\nbody\n")?,
+            Some((FrontMatter::default(), "body\n"))
+        );
+        assert_eq!(
+            strip_front_matter(
+                file,
+                "---tempura\npath = \"bin/{{name}}\"\nif = \"!skip\"\nmode = 0o755\ndelimiters = \"<% %>\"\nconflict = \"skip\"\n---\nbody\n"
+            )?,
+            Some((
+                FrontMatter {
+                    path: Some("bin/{{name}}".to_string()),
+                    condition: Some("!skip".to_string()),
+                    mode: Some(0o755),
+                    delimiters: Some("<% %>".to_string()),
+                    conflict: Some(ConflictPolicy::Skip),
+                    inject: None,
+                    merge: None,
+                },
+                "body\n"
+            ))
+        );
+        assert_eq!(
+            strip_front_matter(
+                file,
+                "---tempura\nmerge = \"keep\"\n[inject]\nmode = { before = \"// end\" }\n---\n"
+            )?
+            .map(|(front_matter, _)| (front_matter.inject, front_matter.merge)),
+            Some((
+                Some(Injection {
+                    mode: crate::InjectMode::Before("// end".to_string()),
+                    skip_if: None,
+                }),
+                Some(MergeMode::Keep)
+            ))
+        );
+        assert_eq!(
+            strip_front_matter(file, "---tempura\nmode = \"0644\"\n---")?
+                .map(|(front_matter, _)| front_matter.mode),
+            Some(Some(0o644))
+        );
+        for content in [
+            "---tempura\nbody\n",
+            "---tempura\nunknown = 1\n---\n",
+            "---tempura\nmode = 0o17777\n---\n",
+            "---tempura\nmode = \"999\"\n---\n",
+            "---tempura\nmode = \"0o755\"\n---\n",
+            "---tempura\nmode = -1\n---\n",
+            "---tempura\npath: a\n---\n",
+        ] {
+            assert!(matches!(
+                strip_front_matter(file, content),
+                Err(Error::FrontMatterIsNotValid(_))
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_emitted() {
+        let data = [
+            ("ci".to_string(), "true".to_string()),
+            ("docker".to_string(), "false".to_string()),
+        ]
+        .into_iter()
+        .collect::<Data>();
+        let f = |condition: Option<&str>| {
+            FrontMatter {
+                condition: condition.map(str::to_string),
+                ..FrontMatter::default()
+            }
+            .is_emitted(&data)
+        };
+        assert!(f(None));
+        assert!(f(Some("ci")));
+        assert!(!f(Some("docker")));
+        assert!(!f(Some("unknown")));
+        assert!(f(Some("!docker")));
+        assert!(!f(Some("!ci")));
+    }
+
+    #[test]
+    fn test_output_path() {
+        let file = Path::new("a");
+        assert_eq!(
+            output_path(file, "./bin/a.sh").ok(),
+            Some(PathBuf::from("bin/a.sh"))
+        );
+        assert!(output_path(file, "").is_err());
+        assert!(output_path(file, "/etc/passwd").is_err());
+        assert!(output_path(file, "../a").is_err());
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{
+    archive::normalize,
+    source::{Entry, MemorySource, Source},
+    Error,
+};
+
+/// The separator between the repository and the ref in a template argument (e.g. `tmpl#v1.0.0`).
+const REF_SEPARATOR: char = '#';
+
+/// Reads the template files from the tree of a local git repository at a ref (branch, tag or commit).
+///
+/// The working tree is not checked out and the `.gitattributes` (e.g. `export-ignore`) are not
+/// applied.
+#[derive(Debug)]
+pub struct GitSource {
+    files: MemorySource,
+    modes: BTreeMap<PathBuf, u32>,
+    repository: PathBuf,
+    commit: String,
+}
+
+impl GitSource {
+    pub fn open(repository: &Path, rev: &str) -> Result<Self, Error> {
+        if !repository.is_dir() {
+            return Err(Error::TemplateNotFound);
+        }
+        if rev.is_empty() || rev.starts_with('-') {
+            return Err(Error::GitFailed(format!("invalid ref: {}", rev)));
+        }
+        let commit = git(
+            repository,
+            &[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("{}^{{commit}}", rev),
+            ],
+            None,
+        )
+        .map_err(|_| Error::GitFailed(format!("ref not found: {}", rev)))?;
+        let commit = String::from_utf8_lossy(commit.as_slice())
+            .trim()
+            .to_string();
+        let (files, modes) = read_tree(repository, commit.as_str())?;
+        Ok(Self {
+            files,
+            modes,
+            repository: repository.to_path_buf(),
+            commit,
+        })
+    }
+
+    /// Returns the repository.
+    pub fn repository(&self) -> &Path {
+        self.repository.as_path()
+    }
+
+    /// Returns the commit id the ref was resolved to.
+    pub fn commit(&self) -> &str {
+        self.commit.as_str()
+    }
+}
+
+impl Source for GitSource {
+    fn read_dir(&self, dir: &Path) -> Result<Vec<Entry>, Error> {
+        self.files.read_dir(dir)
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Option<Vec<u8>>, Error> {
+        self.files.read_file(path)
+    }
+
+    fn mode(&self, path: &Path) -> Option<u32> {
+        self.modes.get(path).copied()
+    }
+
+    fn commit(&self) -> Option<&str> {
+        Some(self.commit.as_str())
+    }
+}
+
+/// Splits a template argument into the repository and the ref (e.g. `tmpl#v1.0.0`).
+pub(crate) fn split_ref(template: &str) -> Option<(&str, &str)> {
+    template
+        .rsplit_once(REF_SEPARATOR)
+        .filter(|(repository, rev)| !repository.is_empty() && !rev.is_empty())
+}
+
+/// Reads the regular files (not symbolic links or submodules) in the tree of `commit` and their
+/// permissions.
+fn read_tree(
+    repository: &Path,
+    commit: &str,
+) -> Result<(MemorySource, BTreeMap<PathBuf, u32>), Error> {
+    let invalid =
+        |s: &[u8]| Error::GitFailed(format!("invalid entry: {}", String::from_utf8_lossy(s)));
+    // e.g. `100755 blob <object>\t<path>\0`
+    let tree = git(repository, &["ls-tree", "-r", "-z", commit], None)?;
+    let mut entries = vec![];
+    for entry in tree
+        .split(|b| *b == b'\0')
+        .filter(|entry| !entry.is_empty())
+    {
+        let tab = entry
+            .iter()
+            .position(|b| *b == b'\t')
+            .ok_or_else(|| invalid(entry))?;
+        let info = std::str::from_utf8(&entry[..tab]).map_err(|_| invalid(entry))?;
+        let [mode, "blob", object] = info.split(' ').collect::<Vec<&str>>()[..] else {
+            continue;
+        };
+        if !matches!(mode, "100644" | "100755") {
+            continue;
+        }
+        let mode = u32::from_str_radix(mode, 8).map_err(|_| invalid(entry))? & 0o7777;
+        #[cfg(unix)]
+        let path = PathBuf::from(
+            <std::ffi::OsStr as std::os::unix::ffi::OsStrExt>::from_bytes(&entry[tab + 1..]),
+        );
+        #[cfg(not(unix))]
+        let path = PathBuf::from(String::from_utf8_lossy(&entry[tab + 1..]).into_owned());
+        let path = normalize(path.as_path()).ok_or_else(|| invalid(entry))?;
+        entries.push((path, mode, object));
+    }
+
+    // e.g. `<object> blob <size>\n<content>\n` for each object
+    let objects = entries
+        .iter()
+        .map(|(_, _, object)| format!("{}\n", object))
+        .collect::<String>();
+    let output = git(
+        repository,
+        &["cat-file", "--batch"],
+        Some(objects.into_bytes()),
+    )?;
+    let mut files = MemorySource::default();
+    let mut modes = BTreeMap::new();
+    let mut rest = output.as_slice();
+    for (path, mode, _) in entries {
+        let newline = rest
+            .iter()
+            .position(|b| *b == b'\n')
+            .ok_or_else(|| invalid(rest))?;
+        let size = std::str::from_utf8(&rest[..newline])
+            .ok()
+            .and_then(|header| header.rsplit(' ').next())
+            .and_then(|size| size.parse::<usize>().ok())
+            .filter(|size| newline + 1 + size < rest.len())
+            .ok_or_else(|| invalid(&rest[..newline]))?;
+        let content = rest[newline + 1..newline + 1 + size].to_vec();
+        rest = &rest[newline + 1 + size + 1..];
+        modes.insert(path.clone(), mode);
+        files.files.insert(path, content);
+    }
+    Ok((files, modes))
+}
+
+fn git(repository: &Path, args: &[&str], input: Option<Vec<u8>>) -> Result<Vec<u8>, Error> {
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(repository)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::GitFailed(e.to_string()))?;
+    // writes the input in another thread not to block on the full stdout
+    let stdin = child.stdin.take().expect("stdin to be piped");
+    let writer = std::thread::spawn(move || {
+        let mut stdin = stdin;
+        stdin.write_all(input.unwrap_or_default().as_slice())
+    });
+    let output = child
+        .wait_with_output()
+        .map_err(|e| Error::GitFailed(e.to_string()))?;
+    writer
+        .join()
+        .expect("writer not to panic")
+        .map_err(|e| Error::GitFailed(e.to_string()))?;
+    if !output.status.success() {
+        return Err(Error::GitFailed(
+            String::from_utf8_lossy(output.stderr.as_slice())
+                .trim()
+                .to_string(),
+        ));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_ref() {
+        assert_eq!(split_ref("tmpl#v1"), Some(("tmpl", "v1")));
+        assert_eq!(split_ref("a#b/tmpl#main"), Some(("a#b/tmpl", "main")));
+        assert_eq!(split_ref("tmpl"), None);
+        assert_eq!(split_ref("tmpl#"), None);
+        assert_eq!(split_ref("#v1"), None);
+    }
+}
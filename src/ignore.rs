@@ -0,0 +1,249 @@
+use std::path::{Path, PathBuf};
+
+pub const IGNORE_FILE_NAME: &str = ".tempuraignore";
+
+/// The version control metadata ignored by default (`.git` can also be a file in worktrees and submodules).
+const VCS_RULES: &str = ".git\n.hg/\n.svn/\n.bzr/\nCVS/\n_darcs/\n.jj/\n.pijul/\n";
+
+/// The rules of the `.tempuraignore` files from the template root down to the current directory.
+#[derive(Clone, Debug, Default)]
+pub struct Ignore {
+    rule_sets: Vec<(PathBuf, Vec<Rule>)>,
+}
+
+impl Ignore {
+    /// Returns a new `Ignore` that ignores the version control metadata (e.g. `.git`).
+    pub fn vcs() -> Self {
+        Self::default().with_dir(Path::new(""), Some(VCS_RULES))
+    }
+
+    /// Returns a new `Ignore` extended with the rules in `<dir>/.tempuraignore` (if any).
+    ///
+    /// `dir` must be relative to the template root.
+    pub fn with_dir(&self, dir: &Path, rules: Option<&str>) -> Self {
+        let mut ignore = self.clone();
+        if let Some(rules) = rules {
+            ignore.rule_sets.push((
+                dir.to_path_buf(),
+                rules.lines().filter_map(Rule::parse).collect(),
+            ));
+        }
+        ignore
+    }
+
+    /// Returns `true` if `path` (relative to the template root) is ignored.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if path.file_name().and_then(|s| s.to_str()) == Some(IGNORE_FILE_NAME) {
+            return true;
+        }
+        let mut ignored = false;
+        for (base, rules) in &self.rule_sets {
+            let Ok(relative) = path.strip_prefix(base) else {
+                continue;
+            };
+            let relative = to_slash(relative);
+            for rule in rules {
+                if rule.matches(relative.as_str(), is_dir) {
+                    ignored = !rule.negated;
+                }
+            }
+        }
+        ignored
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    dir_only: bool,
+    negated: bool,
+    pattern: String,
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        if line.is_empty() || line == "/" {
+            return None;
+        }
+        Some(Self {
+            dir_only,
+            negated,
+            pattern: line.to_string(),
+        })
+    }
+
+    fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        path_match(self.pattern.as_str(), path)
+    }
+}
+
+/// Matches a `/`-separated relative `path` against a gitignore-style `pattern`.
+///
+/// A pattern containing `/` is matched against the whole path, otherwise against the file name.
+pub fn path_match(pattern: &str, path: &str) -> bool {
+    if let Some(pattern) = pattern.strip_prefix('/') {
+        glob_match(pattern, path)
+    } else if pattern.contains('/') {
+        glob_match(pattern, path)
+    } else {
+        let file_name = path.rsplit('/').next().unwrap_or(path);
+        glob_match(pattern, file_name)
+    }
+}
+
+/// Converts `path` to a `/`-separated string.
+pub fn to_slash(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Matches a `/`-separated `path` against a gitignore-style glob `pattern`.
+///
+/// `*` and `?` do not match `/`, `**` matches any number of directories and `[...]` matches a character class.
+pub fn glob_match(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<char>>();
+    let path = path.chars().collect::<Vec<char>>();
+    glob_match_sub(&pattern, &path)
+}
+
+fn glob_match_sub(p: &[char], s: &[char]) -> bool {
+    match p {
+        [] => s.is_empty(),
+        ['*', '*', '/', rest @ ..] => (0..=s.len())
+            .filter(|&i| i == 0 || s[i - 1] == '/')
+            .any(|i| glob_match_sub(rest, &s[i..])),
+        ['*', '*', rest @ ..] => (0..=s.len()).any(|i| glob_match_sub(rest, &s[i..])),
+        ['*', rest @ ..] => (0..=s.len())
+            .take_while(|&i| i == 0 || s[i - 1] != '/')
+            .any(|i| glob_match_sub(rest, &s[i..])),
+        ['?', rest @ ..] => matches!(s, [c, ..] if *c != '/') && glob_match_sub(rest, &s[1..]),
+        ['[', rest @ ..] => match (parse_class(rest), s) {
+            (Some((class, rest)), [c, s_rest @ ..]) => {
+                *c != '/' && class.matches(*c) && glob_match_sub(rest, s_rest)
+            }
+            (Some(_), []) => false,
+            (None, _) => matches!(s, ['[', ..]) && glob_match_sub(rest, &s[1..]),
+        },
+        ['\\', c, rest @ ..] => matches!(s, [x, ..] if x == c) && glob_match_sub(rest, &s[1..]),
+        [c, rest @ ..] => matches!(s, [x, ..] if x == c) && glob_match_sub(rest, &s[1..]),
+    }
+}
+
+struct Class {
+    negated: bool,
+    ranges: Vec<(char, char)>,
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        self.ranges.iter().any(|(from, to)| *from <= c && c <= *to) != self.negated
+    }
+}
+
+fn parse_class(p: &[char]) -> Option<(Class, &[char])> {
+    let (negated, mut p) = match p {
+        ['!' | '^', rest @ ..] => (true, rest),
+        _ => (false, p),
+    };
+    let mut ranges = vec![];
+    let mut first = true;
+    loop {
+        match p {
+            [] => return None,
+            [']', rest @ ..] if !first => return Some((Class { negated, ranges }, rest)),
+            [from, '-', to, rest @ ..] if *to != ']' => {
+                ranges.push((*from, *to));
+                p = rest;
+            }
+            [c, rest @ ..] => {
+                ranges.push((*c, *c));
+                p = rest;
+            }
+        }
+        first = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        let f = glob_match;
+        assert!(f("a", "a"));
+        assert!(!f("a", "b"));
+        assert!(f("*.txt", "a.txt"));
+        assert!(!f("*.txt", "a/b.txt"));
+        assert!(f("?.txt", "a.txt"));
+        assert!(!f("?.txt", "ab.txt"));
+        assert!(f("[ab].txt", "a.txt"));
+        assert!(!f("[!ab].txt", "a.txt"));
+        assert!(f("[a-c].txt", "b.txt"));
+        assert!(f("**/b.txt", "b.txt"));
+        assert!(f("**/b.txt", "a/b.txt"));
+        assert!(f("**/b.txt", "a/a/b.txt"));
+        assert!(!f("**/b.txt", "ab.txt"));
+        assert!(f("a/**", "a/b/c"));
+        assert!(f("a/**/c", "a/c"));
+        assert!(f("a/**/c", "a/b/c"));
+        assert!(f(r"\*", "*"));
+        assert!(!f(r"\*", "a"));
+    }
+
+    #[test]
+    fn test_ignore() {
+        let ignore = Ignore::default().with_dir(
+            Path::new(""),
+            Some("# comment\n*.bak\n/README.md\ndocs/\n!keep.bak\nsub/*.txt\n"),
+        );
+        let f = |p: &str, is_dir: bool| ignore.is_ignored(Path::new(p), is_dir);
+        assert!(f(".tempuraignore", false));
+        assert!(f("a/.tempuraignore", false));
+        assert!(f("a.bak", false));
+        assert!(f("a/b.bak", false));
+        assert!(!f("keep.bak", false));
+        assert!(f("README.md", false));
+        assert!(!f("a/README.md", false));
+        assert!(f("docs", true));
+        assert!(!f("docs", false));
+        assert!(f("sub/a.txt", false));
+        assert!(!f("a/sub/a.txt", false));
+
+        let ignore = ignore.with_dir(Path::new("a"), Some("!b.bak\nc.txt\n"));
+        let f = |p: &str| ignore.is_ignored(Path::new(p), false);
+        assert!(!f("a/b.bak"));
+        assert!(f("a/c.txt"));
+        assert!(!f("c.txt"));
+    }
+
+    #[test]
+    fn test_ignore_vcs() {
+        let ignore = Ignore::vcs();
+        let f = |p: &str, is_dir: bool| ignore.is_ignored(Path::new(p), is_dir);
+        assert!(f(".git", true));
+        assert!(f(".git", false));
+        assert!(f("a/.git", true));
+        assert!(f(".hg", true));
+        assert!(f("CVS", true));
+        assert!(!f("CVS", false));
+        assert!(!f(".gitignore", false));
+        assert!(!f(".github", true));
+    }
+}
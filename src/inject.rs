@@ -0,0 +1,177 @@
+use crate::{Error, OutputFile};
+
+/// Where a rendered snippet is injected into the existing file.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InjectMode {
+    /// At the end of the file.
+    Append,
+    /// At the beginning of the file.
+    Prepend,
+    /// Before the first line matching the regex.
+    Before(String),
+    /// After the first line matching the regex.
+    After(String),
+}
+
+/// How a rendered file is injected into the existing file instead of being created.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Injection {
+    pub mode: InjectMode,
+    /// The regex to skip the injection if the existing file matches it. Without it, the injection
+    /// is skipped if the existing file already contains the snippet.
+    #[serde(default)]
+    pub skip_if: Option<String>,
+}
+
+impl Injection {
+    /// Returns an error if the regexes are not valid.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        let anchor = match &self.mode {
+            InjectMode::Before(anchor) | InjectMode::After(anchor) => Some(anchor),
+            InjectMode::Append | InjectMode::Prepend => None,
+        };
+        for pattern in anchor.into_iter().chain(self.skip_if.as_ref()) {
+            regex::Regex::new(pattern).map_err(|e| Error::ManifestIsNotValid(e.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Injects the rendered snippet into `existing` (empty if the file does not exist).
+///
+/// Returns `None` if the rendered file is not injected (or is not UTF-8).
+pub(crate) fn apply_injection(
+    existing: &[u8],
+    output_file: &OutputFile,
+) -> Result<Option<Vec<u8>>, Error> {
+    let Some(injection) = output_file.inject.as_ref() else {
+        return Ok(None);
+    };
+    let Ok(snippet) = std::str::from_utf8(output_file.content.as_slice()) else {
+        return Ok(None);
+    };
+    let path = output_file.path.display().to_string();
+    let existing =
+        std::str::from_utf8(existing).map_err(|_| Error::ReadFileFailed(path.clone()))?;
+    let regex = |pattern: &str| {
+        regex::Regex::new(pattern).map_err(|e| Error::ManifestIsNotValid(e.to_string()))
+    };
+
+    let skip = match injection.skip_if.as_deref() {
+        Some(skip_if) => regex(skip_if)?.is_match(existing),
+        None => !snippet.trim().is_empty() && existing.contains(snippet.trim()),
+    };
+    if skip {
+        return Ok(Some(existing.as_bytes().to_vec()));
+    }
+
+    let mut snippet = snippet.to_string();
+    if !snippet.is_empty() && !snippet.ends_with('\n') {
+        snippet.push('\n');
+    }
+    let injected = match &injection.mode {
+        InjectMode::Append => {
+            let mut injected = existing.to_string();
+            if !injected.is_empty() && !injected.ends_with('\n') {
+                injected.push('\n');
+            }
+            injected + snippet.as_str()
+        }
+        InjectMode::Prepend => snippet + existing,
+        InjectMode::Before(anchor) | InjectMode::After(anchor) => {
+            let regex = regex(anchor)?;
+            let mut offset = 0;
+            let mut injected = None;
+            for line in existing.split_inclusive('\n') {
+                if regex.is_match(line.trim_end_matches(['\r', '\n'])) {
+                    let at = match injection.mode {
+                        InjectMode::Before(_) => offset,
+                        _ => offset + line.len(),
+                    };
+                    let (head, tail) = existing.split_at(at);
+                    let mut head = head.to_string();
+                    if !head.is_empty() && !head.ends_with('\n') {
+                        head.push('\n');
+                    }
+                    injected = Some(head + snippet.as_str() + tail);
+                    break;
+                }
+                offset += line.len();
+            }
+            injected.ok_or_else(|| Error::AnchorNotFound(path, anchor.clone()))?
+        }
+    };
+    Ok(Some(injected.into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f(
+        existing: &str,
+        snippet: &str,
+        mode: InjectMode,
+        skip_if: Option<&str>,
+    ) -> Result<String, Error> {
+        let output_file = OutputFile {
+            inject: Some(Injection {
+                mode,
+                skip_if: skip_if.map(str::to_string),
+            }),
+            ..OutputFile::new("mod.rs", snippet)
+        };
+        Ok(
+            String::from_utf8(apply_injection(existing.as_bytes(), &output_file)?.unwrap())
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_apply_injection() -> Result<(), Error> {
+        use InjectMode::*;
+        assert_eq!(f("mod a;\n", "mod b;\n", Append, None)?, "mod a;\nmod b;\n");
+        assert_eq!(f("mod a;", "mod b;", Append, None)?, "mod a;\nmod b;\n");
+        assert_eq!(f("", "mod b;\n", Append, None)?, "mod b;\n");
+        assert_eq!(
+            f("mod a;\n", "mod b;\n", Prepend, None)?,
+            "mod b;\nmod a;\n"
+        );
+        let routes = "route(a);\n// routes end\n";
+        let before = Before("// routes end".to_string());
+        assert_eq!(
+            f(routes, "route(b);\n", before.clone(), None)?,
+            "route(a);\nroute(b);\n// routes end\n"
+        );
+        assert_eq!(
+            f(
+                "[dependencies]\nserde = \"1\"\n",
+                "regex = \"1\"\n",
+                After(r"^\[dependencies\]$".to_string()),
+                None
+            )?,
+            "[dependencies]\nregex = \"1\"\nserde = \"1\"\n"
+        );
+        // idempotent
+        assert_eq!(
+            f("mod a;\nmod b;\n", "mod b;\n", Append, None)?,
+            "mod a;\nmod b;\n"
+        );
+        assert_eq!(
+            f(
+                "regex = \"1.10\"\n",
+                "regex = \"1\"\n",
+                Append,
+                Some("(?m)^regex =")
+            )?,
+            "regex = \"1.10\"\n"
+        );
+        assert!(matches!(
+            f("a\n", "b\n", before, None),
+            Err(Error::AnchorNotFound(_, anchor)) if anchor == "// routes end"
+        ));
+        Ok(())
+    }
+}
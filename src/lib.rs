@@ -0,0 +1,121 @@
+//! The tempura is a template engine designed to work with the file system (files and directories).
+//!
+//! ```
+//! # fn main() -> Result<(), tempura::Error> {
+//! let data = [("name".to_string(), "World".to_string())].into_iter().collect();
+//! assert_eq!(tempura::render("Hello,{{name}}", &data)?, "Hello,World");
+//! # Ok(())
+//! # }
+//! ```
+
+mod answers;
+mod archive;
+mod diff;
+mod dir;
+mod front_matter;
+mod git;
+mod ignore;
+mod inject;
+mod manifest;
+mod merge;
+mod output;
+mod region;
+mod source;
+mod template;
+mod update;
+
+use std::collections::BTreeMap;
+
+pub use answers::{AnsweredTemplate, Answers, ANSWERS_FILE_NAME};
+pub use archive::{ArchiveFormat, ArchiveOutput, ArchiveSource};
+pub use dir::{CheckStatus, OutputFile, Rendered, TemplateDir};
+pub use git::GitSource;
+pub use inject::{InjectMode, Injection};
+pub use merge::MergeMode;
+pub use output::{ConflictPolicy, FsOutput, MemoryOutput, Output, StdoutOutput};
+pub use source::{DirSource, EmbeddedSource, Entry, MemorySource, Source};
+pub use template::{render, Delimiters, Diagnostic, Template};
+pub use update::{update, UpdateStatus};
+
+/// The data to render templates with (variable name to value).
+pub type Data = BTreeMap<String, String>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("anchor not found: {0} {1}")]
+    AnchorNotFound(String, String),
+    #[error("answers is not valid: {0}")]
+    AnswersIsNotValid(String),
+    #[error("archive is not valid: {0}")]
+    ArchiveIsNotValid(String),
+    #[error("create directory failed: {0}")]
+    CreateDirectoryFailed(String),
+    #[error("create file failed: {0}")]
+    CreateFileFailed(String),
+    #[error("current directory not found")]
+    CurrentDirectoryNotFound,
+    #[error("document is not valid: {0}")]
+    DocumentIsNotValid(String),
+    #[error("extends cycle: {0}")]
+    ExtendsCycle(String),
+    #[error("extends is not supported by the template source: {0}")]
+    ExtendsNotSupported(String),
+    #[error("file block is not valid: {0}")]
+    FileBlockIsNotValid(String),
+    #[error("front matter is not valid: {0}")]
+    FrontMatterIsNotValid(String),
+    #[error("git failed: {0}")]
+    GitFailed(String),
+    #[error("include cycle: {0}")]
+    IncludeCycle(String),
+    #[error("input is not UTF-8")]
+    InputIsNotUtf8,
+    #[error("input is not valid json")]
+    InputIsNotValidJson,
+    #[error("invalid delimiters: {0}")]
+    InvalidDelimiters(String),
+    #[error("malformed tags: {0:?}")]
+    MalformedTags(Vec<String>),
+    #[error("manifest is not valid: {0}")]
+    ManifestIsNotValid(String),
+    #[error("merge conflicts: {0:?}")]
+    MergeConflicts(Vec<String>),
+    #[error("no arguments")]
+    NoArguments,
+    #[error("option value not found: {0}")]
+    OptionValueNotFound(String),
+    #[error("output is stale: {0:?}")]
+    OutputIsStale(Vec<String>),
+    #[error("partial not found: {0}")]
+    PartialNotFound(String),
+    #[error("read directory failed: {0}")]
+    ReadDirectoryFailed(String),
+    #[error("read file failed: {0}")]
+    ReadFileFailed(String),
+    #[error("region not found: {0} {1}")]
+    RegionNotFound(String, String),
+    #[error("template dir name is not UTF-8 {0}")]
+    TemplateDirNameIsNotUtf8(String),
+    #[error("template file name is not UTF-8 {0}")]
+    TemplateFileNameIsNotUtf8(String),
+    #[error("template is not directory")]
+    TemplateIsNotDirectory,
+    #[error("template is not versioned: {0}")]
+    TemplateIsNotVersioned(String),
+    #[error("template not found")]
+    TemplateNotFound,
+    #[error("unknown archive format: {0}")]
+    UnknownArchiveFormat(String),
+    #[error("unknown document format: {0}")]
+    UnknownDocumentFormat(String),
+    #[error("unknown option: {0}")]
+    UnknownOption(String),
+    #[error("variable contains path separator: {0} {1}")]
+    VariableContainsPathSeparator(String, String),
+    #[error("variable is not valid path: {0} {1}")]
+    VariableIsNotValidPath(String, String),
+    #[error("variable not found: {0}")]
+    VariableNotFound(String),
+    #[error("write file failed: {0}")]
+    WriteFileFailed(String),
+}
@@ -1,328 +1,302 @@
 use std::{
-    collections::BTreeMap,
-    io::{Read, Write},
+    io::Read,
     path::{Path, PathBuf},
 };
 
-#[derive(Debug, thiserror::Error)]
-enum Error {
-    #[error("create directory failed: {0}")]
-    CreateDirectoryFailed(String),
-    #[error("create file failed: {0}")]
-    CreateFileFailed(String),
-    #[error("current directory not found")]
-    CurrentDirectoryNotFound,
-    #[error("input is not UTF-8")]
-    InputIsNotUtf8,
-    #[error("input is not valid json")]
-    InputIsNotValidJson,
-    #[error("no arguments")]
-    NoArguments,
-    #[error("read directory failed: {0}")]
-    ReadDirectoryFailed(String),
-    #[error("read file failed: {0}")]
-    ReadFileFailed(String),
-    #[error("template dir name is not UTF-8 {0}")]
-    TemplateDirNameIsNotUtf8(String),
-    #[error("template file name is not UTF-8 {0}")]
-    TemplateFileNameIsNotUtf8(String),
-    #[error("template is not directory")]
-    TemplateIsNotDirectory,
-    #[error("template not found")]
-    TemplateNotFound,
-    #[error("variable contains path separator: {0} {1}")]
-    VariableContainsPathSeparator(String, String),
-    #[error("variable not found: {0}")]
-    VariableNotFound(String),
-    #[error("write file failed: {0}")]
-    WriteFileFailed(String),
-}
+use tempura::{
+    AnsweredTemplate, Answers, ArchiveFormat, ArchiveOutput, Data, Delimiters, Error, FsOutput,
+    Rendered, StdoutOutput, TemplateDir, UpdateStatus, ANSWERS_FILE_NAME,
+};
 
 fn main() -> Result<(), Error> {
-    let args = std::env::args().collect::<Vec<String>>();
-    if args.len() < 2 {
-        return Err(Error::NoArguments);
+    let mut args = std::env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("check") => check(args.skip(1)),
+        Some("diff") => diff(args.skip(1)),
+        Some("update") => update(args.skip(1)),
+        _ => generate(args),
+    }
+}
+
+fn generate(mut args: impl Iterator<Item = String>) -> Result<(), Error> {
+    let mut templates = vec![];
+    let mut archive = None;
+    let mut archive_format = None;
+    let mut delimiters = None;
+    let mut answers_file = None;
+    let mut include_vcs = false;
+    let mut save_answers = false;
+    let mut strict = false;
+    let mut stdout = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--archive" => {
+                archive = Some(args.next().ok_or(Error::OptionValueNotFound(arg))?);
+            }
+            "--archive-format" => {
+                let value = args.next().ok_or(Error::OptionValueNotFound(arg))?;
+                archive_format = Some(value.parse::<ArchiveFormat>()?);
+            }
+            "--delimiters" => {
+                delimiters = Some(args.next().ok_or(Error::OptionValueNotFound(arg))?);
+            }
+            "--from-answers" => {
+                answers_file = Some(PathBuf::from(
+                    args.next().ok_or(Error::OptionValueNotFound(arg))?,
+                ));
+            }
+            "--include-vcs" => include_vcs = true,
+            "--save-answers" => save_answers = true,
+            "--stdout" => stdout = true,
+            "--strict" => strict = true,
+            // the rest are templates (e.g. `-- check`)
+            "--" => templates.extend(args.by_ref()),
+            _ if arg.starts_with("--") => return Err(Error::UnknownOption(arg)),
+            _ => templates.push(arg),
+        }
     }
 
-    let template = PathBuf::from(args[1].as_str())
-        .canonicalize()
-        .map_err(|_| Error::TemplateNotFound)?;
-    if !template.is_dir() {
-        return Err(Error::TemplateIsNotDirectory);
+    // the arguments and the options take precedence over the answers
+    let answers = match answers_file {
+        Some(file) => Some((Answers::load(file.as_path())?, file)),
+        None => None,
+    };
+    let mut template_paths = templates
+        .iter()
+        .map(PathBuf::from)
+        .collect::<Vec<PathBuf>>();
+    if let Some((answers, file)) = answers.as_ref() {
+        if templates.is_empty() {
+            templates = answers
+                .templates
+                .iter()
+                .map(|answered| answered.template.clone())
+                .collect();
+            template_paths = answers.template_paths(file.parent().unwrap_or(Path::new("")));
+        }
+        if delimiters.is_none() {
+            delimiters = answers.delimiters.clone();
+        }
+        include_vcs |= answers.include_vcs;
+    }
+    if templates.is_empty() {
+        return Err(Error::NoArguments);
     }
-    let template_dir = template;
+
+    let template_dir = TemplateDir::open(
+        template_paths.as_slice(),
+        delimiters
+            .as_deref()
+            .map(str::parse::<Delimiters>)
+            .transpose()?
+            .as_ref(),
+    )?
+    .include_vcs(include_vcs);
     // println!("DEBUG: template_dir = {:?}", template_dir);
 
     let output_dir = std::env::current_dir().map_err(|_| Error::CurrentDirectoryNotFound)?;
     // println!("DEBUG: output_dir = {:?}", output_dir);
 
-    let mut data = String::new();
-    std::io::stdin()
-        .read_to_string(&mut data)
-        .map_err(|_| Error::InputIsNotUtf8)?;
-    let data = serde_json::from_str::<BTreeMap<String, String>>(data.as_str())
-        .map_err(|_| Error::InputIsNotValidJson)?;
+    let data = match answers {
+        Some((answers, _)) => answers.data,
+        None => read_data()?,
+    };
     // println!("DEBUG: data = {:?}", data);
 
-    handle_directory(
-        template_dir.as_path(),
-        template_dir.as_path(),
-        output_dir.as_path(),
-        &data,
-    )?;
-
-    Ok(())
-}
+    let mut rendered = template_dir.render(&data)?;
+    if strict && !rendered.diagnostics.is_empty() {
+        return Err(Error::MalformedTags(rendered.diagnostics));
+    }
+    for diagnostic in rendered.diagnostics.iter() {
+        eprintln!("warning: {}", diagnostic);
+    }
+    if save_answers {
+        let answers = Answers {
+            tempura: env!("CARGO_PKG_VERSION").to_string(),
+            templates: templates
+                .into_iter()
+                .zip(template_dir.commits())
+                .map(|(template, commit)| AnsweredTemplate {
+                    template,
+                    commit: commit.clone(),
+                })
+                .collect(),
+            delimiters,
+            include_vcs,
+            data,
+        };
+        rendered.files.push(answers.to_output_file()?);
+    }
 
-fn handle_directory(
-    dir: &Path,
-    template_dir: &Path,
-    output_dir: &Path,
-    data: &BTreeMap<String, String>,
-) -> Result<(), Error> {
-    let mut paths = dir
-        .read_dir()
-        .and_then(|read_dir| {
-            read_dir
-                .map(|dir_entry_result| dir_entry_result.map(|dir_entry| dir_entry.path()))
-                .collect::<std::io::Result<Vec<PathBuf>>>()
-        })
-        .map_err(|_| Error::ReadDirectoryFailed(dir.display().to_string()))?;
-    paths.sort();
-    for path in paths {
-        if path.is_dir() {
-            handle_directory(&path, template_dir, output_dir, data)?;
+    if let Some(archive) = archive {
+        let format = archive_format
+            .or_else(|| ArchiveFormat::from_path(std::path::Path::new(archive.as_str())))
+            .ok_or_else(|| Error::UnknownArchiveFormat(archive.clone()))?;
+        if archive == "-" {
+            let mut output = ArchiveOutput::new(std::io::stdout(), format);
+            rendered.write(&mut output)?;
+            output.finish()?;
         } else {
-            handle_file(&path, template_dir, output_dir, data)?;
+            let archive = output_dir.join(archive);
+            let file = std::fs::OpenOptions::new()
+                .create_new(true)
+                .write(true)
+                .open(archive.as_path())
+                .map_err(|_| Error::CreateFileFailed(archive.display().to_string()))?;
+            let mut output = ArchiveOutput::new(file, format);
+            rendered.write(&mut output)?;
+            output.finish()?;
         }
+    } else if stdout {
+        rendered.write(&mut StdoutOutput::new())?;
+    } else {
+        rendered.write(&mut FsOutput::new(output_dir.as_path()))?;
     }
+
     Ok(())
 }
 
-fn handle_file(
-    file: &Path,
-    template_dir: &Path,
-    output_dir: &Path,
-    data: &BTreeMap<String, String>,
-) -> Result<(), Error> {
-    // println!("DEBUG: file = {:?}", file);
-
-    let dir = file
-        .parent()
-        .expect("file to have parent")
-        .strip_prefix(template_dir)
-        .expect("file to be in template_dir");
-
-    let file_name = file.file_name().expect("file to have file_name");
-    let file_name = file_name
-        .to_str()
-        .ok_or_else(|| Error::TemplateFileNameIsNotUtf8(file_name.to_string_lossy().to_string()))?;
-    let output_file_name = render(file_name, data)?;
-    if output_file_name.chars().any(std::path::is_separator) {
-        return Err(Error::VariableContainsPathSeparator(
-            dir.join(file_name).display().to_string(),
-            dir.join(output_file_name).display().to_string(),
-        ));
-    }
-    let output_file_parent_dir = {
-        let mut dirs = vec![];
-        let mut p = dir;
-        loop {
-            let dir_name = match p.file_name() {
-                None => break,
-                Some(x) => x,
-            };
-            let dir_name = dir_name.to_str().ok_or_else(|| {
-                Error::TemplateDirNameIsNotUtf8(dir.to_string_lossy().to_string())
-            })?;
-            let output_dir_name = render(dir_name, data)?;
-            if output_dir_name.chars().any(std::path::is_separator) {
-                return Err(Error::VariableContainsPathSeparator(
-                    dir.join(file_name).display().to_string(),
-                    dir.join(output_dir_name).display().to_string(),
-                ));
+/// Updates the project in the current directory to the new template version with three-way merge.
+fn update(mut args: impl Iterator<Item = String>) -> Result<(), Error> {
+    let mut answers_file = PathBuf::from(ANSWERS_FILE_NAME);
+    let mut to = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--answers" => {
+                answers_file = PathBuf::from(args.next().ok_or(Error::OptionValueNotFound(arg))?);
             }
-            dirs.push(output_dir_name);
-            match p.parent() {
-                None => break,
-                Some(x) => p = x,
-            }
-        }
-        dirs.reverse();
-        let mut output_dir = output_dir.to_path_buf();
-        for d in dirs {
-            output_dir = output_dir.join(d);
+            "--to" => to = Some(args.next().ok_or(Error::OptionValueNotFound(arg))?),
+            _ => return Err(Error::UnknownOption(arg)),
         }
-        output_dir
-    };
-    let output_file_path = output_file_parent_dir.join(output_file_name);
+    }
 
-    let file_content =
-        std::fs::read_to_string(file).map_err(|_| Error::ReadFileFailed(file_name.to_string()))?;
-    let output_file_content = render(&file_content, data)?;
+    let output_dir = std::env::current_dir().map_err(|_| Error::CurrentDirectoryNotFound)?;
+    let answers = Answers::load(answers_file.as_path())?;
+    let base_dir = answers_file.parent().unwrap_or(Path::new(""));
+    let mut new_answers = answers.clone();
+    new_answers.retarget(to.as_deref())?;
+    let delimiters = answers
+        .delimiters
+        .as_deref()
+        .map(str::parse::<Delimiters>)
+        .transpose()?;
+    let open = |answers: &Answers| {
+        TemplateDir::open(
+            answers.template_paths(base_dir).as_slice(),
+            delimiters.as_ref(),
+        )
+        .map(|template_dir| template_dir.include_vcs(answers.include_vcs))
+    };
+    let base = open(&answers)?.render(&answers.data)?;
+    let new_template_dir = open(&new_answers)?;
+    let new = new_template_dir.render(&answers.data)?;
+    for diagnostic in new.diagnostics.iter() {
+        eprintln!("warning: {}", diagnostic);
+    }
 
-    // println!("DEBUG: output_file_path = {:?}", output_file_path);
-    // println!("DEBUG: output_file_content = {:?}", output_file_content);
+    let statuses = tempura::update(output_dir.as_path(), &base, &new)?;
+    for (answered, commit) in new_answers
+        .templates
+        .iter_mut()
+        .zip(new_template_dir.commits())
+    {
+        answered.commit = commit.clone();
+    }
+    new_answers.tempura = env!("CARGO_PKG_VERSION").to_string();
+    std::fs::write(
+        answers_file.as_path(),
+        new_answers.to_output_file()?.content,
+    )
+    .map_err(|_| Error::WriteFileFailed(answers_file.display().to_string()))?;
 
-    std::fs::create_dir_all(output_file_parent_dir.as_path())
-        .map_err(|_| Error::CreateDirectoryFailed(output_file_parent_dir.display().to_string()))?;
-    std::fs::OpenOptions::new()
-        .create_new(true)
-        .write(true)
-        .open(output_file_path.as_path())
-        .map_err(|_| Error::CreateFileFailed(output_file_path.display().to_string()))?
-        .write_all(output_file_content.as_bytes())
-        .map_err(|_| Error::WriteFileFailed(output_file_path.display().to_string()))?;
+    let mut conflicts = vec![];
+    for (path, status) in statuses {
+        println!("{}: {}", status, path.display());
+        if status == UpdateStatus::Conflicted {
+            conflicts.push(path.display().to_string());
+        }
+    }
+    if !conflicts.is_empty() {
+        return Err(Error::MergeConflicts(conflicts));
+    }
     Ok(())
 }
 
-fn render(tmpl: &str, data: &BTreeMap<String, String>) -> Result<String, Error> {
-    parse_tmpl(tmpl)
-        .into_iter()
-        .try_fold(String::new(), |acc, token| {
-            Ok(match token {
-                Token::Val(val) => acc + &val,
-                Token::Var(var) => acc + data.get(&var).ok_or(Error::VariableNotFound(var))?,
-            })
-        })
-}
-
-#[derive(Debug, PartialEq)]
-enum Token {
-    Val(String),
-    Var(String),
+/// Prints the unified diff from the files in the directory (the last argument) to the rendered files.
+fn diff(args: impl Iterator<Item = String>) -> Result<(), Error> {
+    let (rendered, output_dir) = render_in_memory(args)?;
+    print!("{}", rendered.diff(output_dir.as_path())?);
+    Ok(())
 }
 
-fn parse_tmpl(s: &str) -> Vec<Token> {
-    let mut tokens = vec![];
-    let mut chars = s.chars();
-    let mut val = String::new();
-    while let Some(c) = chars.next() {
-        if c == '{' {
-            if let Some(c_next) = chars.next() {
-                if c_next == '{' {
-                    val = parse_tmpl_sub(&mut chars, &mut tokens, val);
-                } else {
-                    val.push(c);
-                    val.push(c_next);
-                }
-            } else {
-                val.push(c);
-            }
-        } else {
-            val.push(c);
-        }
+/// Fails if the files in the directory (the last argument) are missing or differ from the rendered files.
+fn check(args: impl Iterator<Item = String>) -> Result<(), Error> {
+    let (rendered, output_dir) = render_in_memory(args)?;
+    let mut stale_files = vec![];
+    for (path, status) in rendered.check(output_dir.as_path())? {
+        println!("{}: {}", status, path.display());
+        stale_files.push(path.display().to_string());
     }
-    if !val.is_empty() {
-        tokens.push(Token::Val(val));
+    if !stale_files.is_empty() {
+        return Err(Error::OutputIsStale(stale_files));
     }
-    tokens
+    Ok(())
 }
 
-fn parse_tmpl_sub(chars: &mut std::str::Chars, tokens: &mut Vec<Token>, mut val: String) -> String {
-    let mut var = String::new();
-    loop {
-        match chars.next() {
-            Some('}') => match chars.next() {
-                Some('}') => {
-                    if var.is_empty() {
-                        val.push_str("{{}}");
-                        break val;
-                    } else if val.is_empty() {
-                        tokens.push(Token::Var(var));
-                        break val;
-                    } else {
-                        tokens.push(Token::Val(val));
-                        tokens.push(Token::Var(var));
-                        return String::new();
-                    }
-                }
-                others => {
-                    val.push_str("{{");
-                    val.push_str(var.as_str());
-                    val.push('}');
-                    if let Some(c) = others {
-                        val.push(c);
-                    }
-                    break val;
-                }
-            },
-            Some(c) if c.is_ascii_alphanumeric() || c == '_' => {
-                var.push(c);
+/// Renders the templates in memory for `diff` and `check`. Returns the rendered files and the
+/// directory to compare with (the last argument).
+fn render_in_memory(mut args: impl Iterator<Item = String>) -> Result<(Rendered, PathBuf), Error> {
+    let mut templates = vec![];
+    let mut data_file = None;
+    let mut delimiters = None;
+    let mut include_vcs = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--data" => {
+                data_file = Some(PathBuf::from(
+                    args.next().ok_or(Error::OptionValueNotFound(arg))?,
+                ));
             }
-            others => {
-                if var.is_empty() && others == Some('"') && {
-                    let mut cs = chars.clone();
-                    cs.next() == Some('{')
-                        && cs.next() == Some('{')
-                        && cs.next() == Some('"')
-                        && cs.next() == Some('}')
-                        && cs.next() == Some('}')
-                } {
-                    chars.next(); // {
-                    chars.next(); // {
-                    chars.next(); // "
-                    chars.next(); // }
-                    chars.next(); // }
-                    val.push_str("{{");
-                    break val;
-                } else {
-                    val.push_str("{{");
-                    val.push_str(var.as_str());
-                    if let Some(c) = others {
-                        val.push(c);
-                    }
-                    break val;
-                }
+            "--delimiters" => {
+                let value = args.next().ok_or(Error::OptionValueNotFound(arg))?;
+                delimiters = Some(value.parse::<Delimiters>()?);
             }
+            "--include-vcs" => include_vcs = true,
+            _ if arg.starts_with("--") => return Err(Error::UnknownOption(arg)),
+            _ => templates.push(arg),
         }
     }
+    let output_dir = PathBuf::from(templates.pop().ok_or(Error::NoArguments)?);
+    if templates.is_empty() {
+        return Err(Error::NoArguments);
+    }
+
+    let template_dir =
+        TemplateDir::open(templates.as_slice(), delimiters.as_ref())?.include_vcs(include_vcs);
+    let data = match data_file {
+        Some(data_file) => read_data_file(data_file.as_path())?,
+        None => read_data()?,
+    };
+    let rendered = template_dir.render(&data)?;
+    for diagnostic in rendered.diagnostics.iter() {
+        eprintln!("warning: {}", diagnostic);
+    }
+    Ok((rendered, output_dir))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Reads the data from the standard input.
+fn read_data() -> Result<Data, Error> {
+    let mut data = String::new();
+    std::io::stdin()
+        .read_to_string(&mut data)
+        .map_err(|_| Error::InputIsNotUtf8)?;
+    serde_json::from_str::<Data>(data.as_str()).map_err(|_| Error::InputIsNotValidJson)
+}
 
-    #[test]
-    fn test_parse_tmpl() {
-        use Token::*;
-        let f = parse_tmpl;
-        let l = |s: &str| -> Token { Val(s.to_string()) };
-        let r = |s: &str| -> Token { Var(s.to_string()) };
-        assert_eq!(f(""), vec![]);
-        assert_eq!(
-            f("ab{{cd}}ef{{gh}}"),
-            vec![l("ab"), r("cd"), l("ef"), r("gh")]
-        );
-        assert_eq!(f("a"), vec![l("a")]);
-        assert_eq!(f("a{"), vec![l("a{")]);
-        assert_eq!(f("a{b"), vec![l("a{b")]);
-        assert_eq!(f("a{{"), vec![l("a{{")]);
-        assert_eq!(f("a{{b"), vec![l("a{{b")]);
-        assert_eq!(f("a{{b}"), vec![l("a{{b}")]);
-        assert_eq!(f("a{{b}c"), vec![l("a{{b}c")]);
-        assert_eq!(f("a{{b}}"), vec![l("a"), r("b")]);
-        assert_eq!(f("a{{b}}c"), vec![l("a"), r("b"), l("c")]);
-        assert_eq!(f("{{a}}"), vec![r("a")]);
-        assert_eq!(f("{{a}}b"), vec![r("a"), l("b")]);
-        assert_eq!(f("{{a}}{"), vec![r("a"), l("{")]);
-        assert_eq!(f("{{a}}{b"), vec![r("a"), l("{b")]);
-        assert_eq!(f("{{a}}{{"), vec![r("a"), l("{{")]);
-        assert_eq!(f("{{a}}{{b"), vec![r("a"), l("{{b")]);
-        assert_eq!(f("{{a}}{{b}"), vec![r("a"), l("{{b}")]);
-        assert_eq!(f("{{a}}{{b}c"), vec![r("a"), l("{{b}c")]);
-        assert_eq!(f("{{a}}{{b}}"), vec![r("a"), r("b")]);
-        assert_eq!(f("{{a}}{{b}}c"), vec![r("a"), r("b"), l("c")]);
-        // escape {{
-        assert_eq!(f(r#"{{""#), vec![l(r#"{{""#)]);
-        assert_eq!(f(r#"{{"{"#), vec![l(r#"{{"{"#)]);
-        assert_eq!(f(r#"{{"{{"#), vec![l(r#"{{"{{"#)]);
-        assert_eq!(f(r#"{{"{{""#), vec![l(r#"{{"{{""#)]);
-        assert_eq!(f(r#"{{"{{"}"#), vec![l(r#"{{"{{"}"#)]);
-        assert_eq!(f(r#"{{"{{"}}"#), vec![l(r#"{{"#)]);
-        assert_eq!(f(r#"{{a"{{"}}"#), vec![l(r#"{{a"{{"}}"#)]);
-        // space is not allowed
-        assert_eq!(f(r#"{{ a }}"#), vec![l(r#"{{ a }}"#)]);
-        assert_eq!(f(r#"{{ "{{" }}"#), vec![l(r#"{{ "{{" }}"#)]);
-    }
+/// Reads the data from `file`.
+fn read_data_file(file: &Path) -> Result<Data, Error> {
+    let data =
+        std::fs::read(file).map_err(|_| Error::ReadFileFailed(file.display().to_string()))?;
+    let data = String::from_utf8(data).map_err(|_| Error::InputIsNotUtf8)?;
+    serde_json::from_str::<Data>(data.as_str()).map_err(|_| Error::InputIsNotValidJson)
 }
@@ -0,0 +1,154 @@
+use std::path::Path;
+
+use crate::{
+    ignore::{path_match, to_slash},
+    source::Source,
+    Delimiters, Error, Injection, MergeMode,
+};
+
+pub const MANIFEST_FILE_NAME: &str = ".tempura.json";
+
+const DEFAULT_PARTIALS_DIR: &str = "_partials";
+
+/// The template settings in `<template_dir>/.tempura.json`.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    delimiters: Option<(String, String)>,
+    extends: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_ordered")]
+    inject: Vec<(String, Injection)>,
+    #[serde(default, deserialize_with = "deserialize_ordered")]
+    merge: Vec<(String, MergeMode)>,
+    partials: Option<String>,
+    #[serde(default)]
+    raw: Vec<String>,
+}
+
+impl Manifest {
+    /// Loads the manifest in `source`. If it does not exist, returns the default manifest.
+    pub fn load(source: &dyn Source) -> Result<Self, Error> {
+        let Some(content) = source.read_file(Path::new(MANIFEST_FILE_NAME))? else {
+            return Ok(Self::default());
+        };
+        let content = String::from_utf8(content)
+            .map_err(|_| Error::ReadFileFailed(MANIFEST_FILE_NAME.to_string()))?;
+        let manifest = serde_json::from_str::<Self>(content.as_str())
+            .map_err(|e| Error::ManifestIsNotValid(e.to_string()))?;
+        for (_, injection) in manifest.inject.iter() {
+            injection.validate()?;
+        }
+        Ok(manifest)
+    }
+
+    pub fn delimiters(&self) -> Result<Option<Delimiters>, Error> {
+        self.delimiters
+            .as_ref()
+            .map(|(open, close)| Delimiters::new(open, close))
+            .transpose()
+    }
+
+    /// Returns the parent template directory (relative to the template root).
+    pub fn extends(&self) -> Option<&Path> {
+        self.extends.as_deref().map(Path::new)
+    }
+
+    /// Returns the injection of the first `inject` pattern (in the declaration order) that `path`
+    /// (relative to the template root) matches.
+    pub fn injection(&self, path: &Path) -> Option<Injection> {
+        let path = to_slash(path);
+        self.inject
+            .iter()
+            .find(|(pattern, _)| path_match(pattern.as_str(), path.as_str()))
+            .map(|(_, injection)| injection.clone())
+    }
+
+    /// Returns the merge mode of the first `merge` pattern (in the declaration order) that `path`
+    /// (relative to the template root) matches.
+    pub fn merge_mode(&self, path: &Path) -> Option<MergeMode> {
+        let path = to_slash(path);
+        self.merge
+            .iter()
+            .find(|(pattern, _)| path_match(pattern.as_str(), path.as_str()))
+            .map(|(_, mode)| *mode)
+    }
+
+    /// Returns the partials directory (relative to the template root).
+    pub fn partials_dir(&self) -> &Path {
+        Path::new(self.partials.as_deref().unwrap_or(DEFAULT_PARTIALS_DIR))
+    }
+
+    /// Returns `true` if `path` (relative to the template root) matches the `raw` patterns.
+    pub fn is_raw(&self, path: &Path) -> bool {
+        let path = to_slash(path);
+        self.raw
+            .iter()
+            .any(|pattern| path_match(pattern.as_str(), path.as_str()))
+    }
+}
+
+/// Deserializes a map into the pairs in the declaration order.
+fn deserialize_ordered<'de, D, T>(deserializer: D) -> Result<Vec<(String, T)>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    struct Visitor<T>(std::marker::PhantomData<T>);
+
+    impl<'de, T: serde::Deserialize<'de>> serde::de::Visitor<'de> for Visitor<T> {
+        type Value = Vec<(String, T)>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a map")
+        }
+
+        fn visit_map<A: serde::de::MapAccess<'de>>(
+            self,
+            mut map: A,
+        ) -> Result<Self::Value, A::Error> {
+            let mut pairs = vec![];
+            while let Some(pair) = map.next_entry()? {
+                pairs.push(pair);
+            }
+            Ok(pairs)
+        }
+    }
+
+    deserializer.deserialize_map(Visitor(std::marker::PhantomData))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InjectMode, MemorySource};
+
+    #[test]
+    fn test_injection() -> Result<(), Error> {
+        let mut source = MemorySource::default();
+        source.files.insert(
+            MANIFEST_FILE_NAME.into(),
+            br#"{"inject":{"b.txt":{"mode":"append"},"*.txt":{"mode":"prepend"}}}"#.to_vec(),
+        );
+        let manifest = Manifest::load(&source)?;
+        let f = |path: &str| manifest.injection(Path::new(path)).map(|it| it.mode);
+        assert_eq!(f("b.txt"), Some(InjectMode::Append));
+        assert_eq!(f("a.txt"), Some(InjectMode::Prepend));
+        assert_eq!(f("a.rs"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_mode() -> Result<(), Error> {
+        let mut source = MemorySource::default();
+        source.files.insert(
+            MANIFEST_FILE_NAME.into(),
+            br#"{"merge":{"b.json":"override","*.json":"keep"}}"#.to_vec(),
+        );
+        let manifest = Manifest::load(&source)?;
+        let f = |path: &str| manifest.merge_mode(Path::new(path));
+        assert_eq!(f("b.json"), Some(MergeMode::Override));
+        assert_eq!(f("a.json"), Some(MergeMode::Keep));
+        assert_eq!(f("a.toml"), None);
+        Ok(())
+    }
+}
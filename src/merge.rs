@@ -0,0 +1,381 @@
+use std::path::Path;
+
+use crate::{Error, OutputFile};
+
+/// How a rendered structured document (JSON, TOML or YAML) is merged into the existing one.
+///
+/// Tables (objects) are merged recursively and the missing keys are added. Arrays are appended
+/// with the missing values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MergeMode {
+    /// The existing values are kept.
+    #[default]
+    Keep,
+    /// The existing values are overridden with the rendered values.
+    Override,
+}
+
+/// The structured document formats detected from the file extensions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// Merges the rendered document into `existing`.
+///
+/// Returns `None` if the rendered file is not merged.
+pub(crate) fn apply_merge(
+    existing: &[u8],
+    output_file: &OutputFile,
+) -> Result<Option<Vec<u8>>, Error> {
+    let Some(mode) = output_file.merge else {
+        return Ok(None);
+    };
+    let path = output_file.path.display().to_string();
+    let format = Format::from_path(output_file.path.as_path())
+        .ok_or_else(|| Error::UnknownDocumentFormat(path.clone()))?;
+    let invalid = |e: &dyn std::fmt::Display| Error::DocumentIsNotValid(format!("{}: {}", path, e));
+    let existing = std::str::from_utf8(existing).map_err(|e| invalid(&e))?;
+    let rendered = std::str::from_utf8(output_file.content.as_slice()).map_err(|e| invalid(&e))?;
+    let merged = match format {
+        Format::Json => {
+            let mut value =
+                serde_json::from_str::<serde_json::Value>(existing).map_err(|e| invalid(&e))?;
+            let new =
+                serde_json::from_str::<serde_json::Value>(rendered).map_err(|e| invalid(&e))?;
+            merge_json(&mut value, new, mode);
+            // keeps the indentation of the existing file
+            let indent = existing
+                .lines()
+                .nth(1)
+                .map(|line| &line[..line.len() - line.trim_start().len()])
+                .filter(|indent| !indent.is_empty())
+                .unwrap_or("  ");
+            let mut merged = vec![];
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+            let mut serializer = serde_json::Serializer::with_formatter(&mut merged, formatter);
+            serde::Serialize::serialize(&value, &mut serializer).map_err(|e| invalid(&e))?;
+            let mut merged = String::from_utf8(merged).map_err(|e| invalid(&e))?;
+            if existing.ends_with('\n') {
+                merged.push('\n');
+            }
+            merged
+        }
+        Format::Toml => {
+            let mut document = existing
+                .parse::<toml_edit::DocumentMut>()
+                .map_err(|e| invalid(&e))?;
+            let new = rendered
+                .parse::<toml_edit::DocumentMut>()
+                .map_err(|e| invalid(&e))?;
+            merge_toml(document.as_table_mut(), new.as_table(), usize::MAX, mode);
+            document.to_string()
+        }
+        Format::Yaml => {
+            // the YAML comments cannot be preserved, so they are not silently dropped
+            if has_yaml_comment(existing) {
+                return Err(invalid(&"the comments of YAML files cannot be merged"));
+            }
+            let load = |s: &str| -> Result<yaml_rust2::Yaml, Error> {
+                let mut documents =
+                    yaml_rust2::YamlLoader::load_from_str(s).map_err(|e| invalid(&e))?;
+                match documents.len() {
+                    0 => Ok(yaml_rust2::Yaml::Hash(Default::default())),
+                    1 => Ok(documents.remove(0)),
+                    _ => Err(invalid(&"multiple YAML documents cannot be merged")),
+                }
+            };
+            let mut value = load(existing)?;
+            merge_yaml(&mut value, load(rendered)?, mode);
+            let mut merged = String::new();
+            let mut emitter = yaml_rust2::YamlEmitter::new(&mut merged);
+            emitter.multiline_strings(true);
+            emitter.dump(&value).map_err(|e| invalid(&e))?;
+            let mut merged = merged
+                .strip_prefix("---\n")
+                .map(str::to_string)
+                .unwrap_or(merged);
+            merged.push('\n');
+            merged
+        }
+    };
+    Ok(Some(merged.into_bytes()))
+}
+
+fn merge_json(value: &mut serde_json::Value, new: serde_json::Value, mode: MergeMode) {
+    use serde_json::Value;
+    match (value, new) {
+        (Value::Object(object), Value::Object(new)) => {
+            for (key, new) in new {
+                match object.get_mut(key.as_str()) {
+                    Some(value) => merge_json(value, new, mode),
+                    None => {
+                        object.insert(key, new);
+                    }
+                }
+            }
+        }
+        (Value::Array(array), Value::Array(new)) => {
+            for new in new {
+                if !array.contains(&new) {
+                    array.push(new);
+                }
+            }
+        }
+        (value, new) => {
+            if mode == MergeMode::Override {
+                *value = new;
+            }
+        }
+    }
+}
+
+fn merge_yaml(value: &mut yaml_rust2::Yaml, new: yaml_rust2::Yaml, mode: MergeMode) {
+    use yaml_rust2::Yaml;
+    match (value, new) {
+        (Yaml::Hash(hash), Yaml::Hash(new)) => {
+            for (key, new) in new {
+                match hash.get_mut(&key) {
+                    Some(value) => merge_yaml(value, new, mode),
+                    None => {
+                        hash.insert(key, new);
+                    }
+                }
+            }
+        }
+        (Yaml::Array(array), Yaml::Array(new)) => {
+            for new in new {
+                if !array.contains(&new) {
+                    array.push(new);
+                }
+            }
+        }
+        (value, new) => {
+            if mode == MergeMode::Override {
+                *value = new;
+            }
+        }
+    }
+}
+
+/// Returns `true` if the YAML document contains a comment (a `#` at the beginning of a token that
+/// is not in a quoted string or a block scalar).
+fn has_yaml_comment(s: &str) -> bool {
+    // the indentation of the line starting the current block scalar
+    let mut block_indent = None;
+    for line in s.lines() {
+        let indent = line.len() - line.trim_start().len();
+        if let Some(block_indent) = block_indent {
+            if line.trim().is_empty() || indent > block_indent {
+                continue;
+            }
+        }
+        block_indent = None;
+        let mut quote = None;
+        let mut prev = ' ';
+        for c in line.chars() {
+            match quote {
+                Some(q) if c == q && !(q == '"' && prev == '\\') => quote = None,
+                Some(_) => {}
+                None if c == '#' && prev.is_whitespace() => return true,
+                None if matches!(c, '"' | '\'')
+                    && (prev.is_whitespace() || matches!(prev, ':' | '-' | ',' | '[' | '{')) =>
+                {
+                    quote = Some(c)
+                }
+                None => {}
+            }
+            prev = c;
+        }
+        // e.g. `key: |`, `- >-` or `key: |2+`
+        let last = line.split_whitespace().last().unwrap_or_default();
+        if last.starts_with(['|', '>'])
+            && last[1..]
+                .chars()
+                .all(|c| matches!(c, '-' | '+' | '1'..='9'))
+        {
+            block_indent = Some(indent);
+        }
+    }
+    false
+}
+
+/// Merges the `new` table into `table` (at the document `position`) preserving the formatting of
+/// `table`.
+fn merge_toml(
+    table: &mut dyn toml_edit::TableLike,
+    new: &dyn toml_edit::TableLike,
+    position: usize,
+    mode: MergeMode,
+) {
+    use toml_edit::{Item, Value};
+    for (key, new) in new.iter() {
+        let Some(item) = table.get_mut(key) else {
+            let mut new = new.clone();
+            // the added tables follow the parent table (or the end of the document)
+            set_toml_position(&mut new, position);
+            table.insert(key, new);
+            continue;
+        };
+        let item_position = item.as_table().and_then(|table| table.position());
+        match (item, new) {
+            (Item::ArrayOfTables(array), Item::ArrayOfTables(new)) => {
+                let existing = array
+                    .iter()
+                    .map(|table| table.to_string())
+                    .collect::<Vec<String>>();
+                for new in new.iter() {
+                    if !existing.contains(&new.to_string()) {
+                        let mut new = new.clone();
+                        new.set_position(usize::MAX);
+                        array.push(new);
+                    }
+                }
+            }
+            (Item::Value(Value::Array(array)), Item::Value(Value::Array(new))) => {
+                for new in new.iter() {
+                    if !array.iter().any(|value| toml_value_eq(value, new)) {
+                        array.push(new.clone());
+                    }
+                }
+            }
+            (item, new) if item.is_table_like() && new.is_table_like() => {
+                if let (Some(table), Some(new)) = (item.as_table_like_mut(), new.as_table_like()) {
+                    merge_toml(table, new, item_position.unwrap_or(position), mode);
+                }
+            }
+            (Item::Value(value), Item::Value(new)) => {
+                if mode == MergeMode::Override && !toml_value_eq(value, new) {
+                    let decor = value.decor().clone();
+                    *value = new.clone();
+                    *value.decor_mut() = decor;
+                }
+            }
+            (item, new) => {
+                if mode == MergeMode::Override {
+                    let mut new = new.clone();
+                    set_toml_position(&mut new, position);
+                    *item = new;
+                }
+            }
+        }
+    }
+}
+
+fn set_toml_position(item: &mut toml_edit::Item, position: usize) {
+    use toml_edit::Item;
+    match item {
+        Item::Table(table) => {
+            table.set_position(position);
+            for (_, item) in table.iter_mut() {
+                set_toml_position(item, position);
+            }
+        }
+        Item::ArrayOfTables(array) => {
+            for table in array.iter_mut() {
+                table.set_position(position);
+            }
+        }
+        Item::None | Item::Value(_) => {}
+    }
+}
+
+/// Compares the TOML values without their formatting.
+fn toml_value_eq(a: &toml_edit::Value, b: &toml_edit::Value) -> bool {
+    let f = |value: &toml_edit::Value| value.clone().decorated("", "").to_string();
+    f(a) == f(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f(path: &str, existing: &str, rendered: &str, mode: MergeMode) -> Result<String, Error> {
+        let output_file = OutputFile {
+            merge: Some(mode),
+            ..OutputFile::new(path, rendered)
+        };
+        Ok(String::from_utf8(apply_merge(existing.as_bytes(), &output_file)?.unwrap()).unwrap())
+    }
+
+    #[test]
+    fn test_apply_merge_json() -> Result<(), Error> {
+        let existing = "{\n    \"name\": \"app\",\n    \"keywords\": [\"a\"],\n    \"scripts\": {\"test\": \"jest\"}\n}\n";
+        let rendered = r#"{"name":"tmpl","keywords":["a","b"],"scripts":{"test":"vitest","lint":"eslint"},"private":true}"#;
+        assert_eq!(
+            f("package.json", existing, rendered, MergeMode::Keep)?,
+            "{\n    \"name\": \"app\",\n    \"keywords\": [\n        \"a\",\n        \"b\"\n    ],\n    \"scripts\": {\n        \"test\": \"jest\",\n        \"lint\": \"eslint\"\n    },\n    \"private\": true\n}\n"
+        );
+        assert!(f("package.json", existing, rendered, MergeMode::Override)?
+            .contains("\"test\": \"vitest\""));
+        assert!(matches!(
+            f("package.json", "{", rendered, MergeMode::Keep),
+            Err(Error::DocumentIsNotValid(_))
+        ));
+        assert!(matches!(
+            f("package.txt", existing, rendered, MergeMode::Keep),
+            Err(Error::UnknownDocumentFormat(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_merge_toml() -> Result<(), Error> {
+        let existing = "[package]\nname = \"app\" # the name\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"1\"\n\n[dev-dependencies]\nanyhow = \"1\"\n";
+        let rendered = "[package]\nname = \"tmpl\"\nkeywords = [\"cli\"]\n\n[dependencies]\nserde = \"1.0.200\"\nregex = \"1\"\n\n[features]\ndefault = []\n";
+        assert_eq!(
+            f("Cargo.toml", existing, rendered, MergeMode::Keep)?,
+            "[package]\nname = \"app\" # the name\nversion = \"0.1.0\"\nkeywords = [\"cli\"]\n\n[dependencies]\nserde = \"1\"\nregex = \"1\"\n\n[dev-dependencies]\nanyhow = \"1\"\n\n[features]\ndefault = []\n"
+        );
+        assert_eq!(
+            f("Cargo.toml", existing, rendered, MergeMode::Override)?,
+            "[package]\nname = \"tmpl\" # the name\nversion = \"0.1.0\"\nkeywords = [\"cli\"]\n\n[dependencies]\nserde = \"1.0.200\"\nregex = \"1\"\n\n[dev-dependencies]\nanyhow = \"1\"\n\n[features]\ndefault = []\n"
+        );
+        assert_eq!(
+            f(
+                "a.toml",
+                "list = [1, 2]\n",
+                "list = [2, 3]\n",
+                MergeMode::Keep
+            )?,
+            "list = [1, 2, 3]\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_merge_yaml() -> Result<(), Error> {
+        let existing = "name: app\nsteps:\n- checkout\non:\n  push: {}\n";
+        let rendered = "name: tmpl\nsteps:\n- checkout\n- test\non:\n  pull_request: {}\n";
+        assert_eq!(
+            f("ci.yml", existing, rendered, MergeMode::Keep)?,
+            "name: app\nsteps:\n  - checkout\n  - test\n\"on\":\n  push: {}\n  pull_request: {}\n"
+        );
+        let existing = "name: \"app #1\"\nrun: |\n  # not a comment\n  make\n";
+        assert_eq!(
+            f("ci.yaml", existing, "name: tmpl\n", MergeMode::Override)?,
+            "name: tmpl\nrun: |\n  # not a comment\n  make\n"
+        );
+        for existing in ["# comment\nname: app\n", "name: app # comment\n"] {
+            assert!(matches!(
+                f("ci.yml", existing, "name: tmpl\n", MergeMode::Keep),
+                Err(Error::DocumentIsNotValid(_))
+            ));
+        }
+        Ok(())
+    }
+}
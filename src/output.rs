@@ -0,0 +1,218 @@
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    inject::apply_injection, merge::apply_merge, region::apply_regions, Error, OutputFile,
+};
+
+/// What to do if the output file already exists (and is not injected, merged or regenerated by
+/// regions).
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Fails with [`Error::CreateFileFailed`].
+    #[default]
+    Error,
+    /// Keeps the existing file.
+    Skip,
+    /// Overwrites the existing file.
+    Overwrite,
+}
+
+/// The destination of the rendered files.
+pub trait Output {
+    fn write_file(&mut self, output_file: &OutputFile) -> Result<(), Error>;
+}
+
+/// Writes the files to a directory. Existing files are not overwritten except for the managed
+/// regions (`tempura:begin <name>` ... `tempura:end <name>`) in them, the injected snippets and the
+/// merged documents.
+#[derive(Debug)]
+pub struct FsOutput {
+    dir: PathBuf,
+}
+
+impl FsOutput {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+}
+
+impl Output for FsOutput {
+    fn write_file(&mut self, output_file: &OutputFile) -> Result<(), Error> {
+        let output_file_path = self.dir.join(output_file.path.as_path());
+        if output_file_path.is_file() {
+            let existing = std::fs::read(output_file_path.as_path())
+                .map_err(|_| Error::ReadFileFailed(output_file_path.display().to_string()))?;
+            if let Some(content) = apply_to_existing(existing.as_slice(), output_file)? {
+                if content != existing {
+                    std::fs::write(output_file_path.as_path(), content).map_err(|_| {
+                        Error::WriteFileFailed(output_file_path.display().to_string())
+                    })?;
+                }
+                return Ok(());
+            }
+            if output_file.conflict == ConflictPolicy::Skip {
+                return Ok(());
+            }
+        }
+        let output_file_parent_dir = output_file_path.parent().expect("path to have parent");
+        std::fs::create_dir_all(output_file_parent_dir).map_err(|_| {
+            Error::CreateDirectoryFailed(output_file_parent_dir.display().to_string())
+        })?;
+        let content = match apply_injection(&[], output_file)? {
+            Some(content) => content,
+            None => output_file.content.clone(),
+        };
+        let mut options = std::fs::OpenOptions::new();
+        if output_file.conflict == ConflictPolicy::Overwrite {
+            options.create(true).truncate(true).write(true);
+        } else {
+            options.create_new(true).write(true);
+        }
+        #[cfg(unix)]
+        if let Some(mode) = output_file.mode {
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, mode);
+        }
+        options
+            .open(output_file_path.as_path())
+            .map_err(|_| Error::CreateFileFailed(output_file_path.display().to_string()))?
+            .write_all(content.as_slice())
+            .map_err(|_| Error::WriteFileFailed(output_file_path.display().to_string()))?;
+        // the mode of the existing file is not changed by `open`
+        #[cfg(unix)]
+        if let (ConflictPolicy::Overwrite, Some(mode)) = (output_file.conflict, output_file.mode) {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(
+                output_file_path.as_path(),
+                std::fs::Permissions::from_mode(mode),
+            )
+            .map_err(|_| Error::WriteFileFailed(output_file_path.display().to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the content to write over the `existing` file: the snippet injected into it, the
+/// document merged into it or the managed regions replaced. Returns `None` if none of them applies
+/// (the file is not overwritten).
+pub(crate) fn apply_to_existing(
+    existing: &[u8],
+    output_file: &OutputFile,
+) -> Result<Option<Vec<u8>>, Error> {
+    match apply_injection(existing, output_file)? {
+        Some(content) => Ok(Some(content)),
+        None => match apply_merge(existing, output_file)? {
+            Some(content) => Ok(Some(content)),
+            None => apply_regions(existing, output_file),
+        },
+    }
+}
+
+/// Keeps the files in memory as a map from the paths to the contents.
+#[derive(Debug, Default)]
+pub struct MemoryOutput {
+    pub files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl Output for MemoryOutput {
+    fn write_file(&mut self, output_file: &OutputFile) -> Result<(), Error> {
+        if self.files.contains_key(output_file.path.as_path()) {
+            return Err(Error::CreateFileFailed(
+                output_file.path.display().to_string(),
+            ));
+        }
+        self.files
+            .insert(output_file.path.clone(), output_file.content.clone());
+        Ok(())
+    }
+}
+
+/// Writes the files to the standard output (or another writer), each preceded by a `==> <path> <==` header.
+#[derive(Debug)]
+pub struct StdoutOutput<W = std::io::Stdout> {
+    writer: W,
+}
+
+impl StdoutOutput {
+    pub fn new() -> Self {
+        Self {
+            writer: std::io::stdout(),
+        }
+    }
+}
+
+impl Default for StdoutOutput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W: Write> StdoutOutput<W> {
+    pub fn with_writer(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> Output for StdoutOutput<W> {
+    fn write_file(&mut self, output_file: &OutputFile) -> Result<(), Error> {
+        let write = |writer: &mut W| -> std::io::Result<()> {
+            writeln!(writer, "==> {} <==", output_file.path.display())?;
+            writer.write_all(output_file.content.as_slice())?;
+            if !output_file.content.is_empty() && !output_file.content.ends_with(b"\n") {
+                writeln!(writer)?;
+            }
+            writer.flush()
+        };
+        write(&mut self.writer)
+            .map_err(|_| Error::WriteFileFailed(output_file.path.display().to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output_file(path: &str, content: &str) -> OutputFile {
+        OutputFile::new(path, content)
+    }
+
+    #[test]
+    fn test_memory_output() -> Result<(), Error> {
+        let mut output = MemoryOutput::default();
+        output.write_file(&output_file("a/b.txt", "b"))?;
+        output.write_file(&output_file("c.txt", "c"))?;
+        assert!(output.write_file(&output_file("c.txt", "d")).is_err());
+        assert_eq!(
+            output.files,
+            [
+                (PathBuf::from("a/b.txt"), b"b".to_vec()),
+                (PathBuf::from("c.txt"), b"c".to_vec()),
+            ]
+            .into_iter()
+            .collect()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stdout_output() -> Result<(), Error> {
+        let mut output = StdoutOutput::with_writer(vec![]);
+        output.write_file(&output_file("a/b.txt", "b"))?;
+        output.write_file(&output_file("c.txt", "c\n"))?;
+        assert_eq!(
+            String::from_utf8(output.into_inner()).unwrap(),
+            "==> a/b.txt <==\nb\n==> c.txt <==\nc\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{diff::lines, Error, OutputFile};
+
+const BEGIN_MARKER: &str = "tempura:begin";
+const END_MARKER: &str = "tempura:end";
+
+/// Replaces the contents of the managed regions in `existing` with the contents of the same regions
+/// in the rendered file.
+///
+/// A managed region is the lines between a line containing `tempura:begin <name>` and a line
+/// containing `tempura:end <name>` (usually in comments like `// tempura:begin mods`). The lines
+/// outside the regions and the marker lines are left as is.
+///
+/// Returns `None` if the rendered file has no managed regions (or is not UTF-8).
+pub(crate) fn apply_regions(
+    existing: &[u8],
+    output_file: &OutputFile,
+) -> Result<Option<Vec<u8>>, Error> {
+    let Ok(rendered) = std::str::from_utf8(output_file.content.as_slice()) else {
+        return Ok(None);
+    };
+    let rendered = lines(rendered);
+    let rendered_regions = find_regions(rendered.as_slice());
+    if rendered_regions.is_empty() {
+        return Ok(None);
+    }
+    let path = output_file.path.display().to_string();
+    let existing =
+        std::str::from_utf8(existing).map_err(|_| Error::ReadFileFailed(path.clone()))?;
+    let existing = lines(existing);
+    let existing_regions = find_regions(existing.as_slice());
+
+    let mut merged = String::new();
+    let mut pos = 0;
+    for (name, begin, end) in existing_regions.iter() {
+        let Some((_, rendered_begin, rendered_end)) =
+            rendered_regions.iter().find(|(it, _, _)| it == name)
+        else {
+            continue;
+        };
+        merged.extend(existing[pos..=*begin].iter().copied());
+        merged.extend(rendered[rendered_begin + 1..*rendered_end].iter().copied());
+        pos = *end;
+    }
+    merged.extend(existing[pos..].iter().copied());
+    for (name, _, _) in rendered_regions {
+        if !existing_regions.iter().any(|(it, _, _)| *it == name) {
+            return Err(Error::RegionNotFound(path, name));
+        }
+    }
+    Ok(Some(merged.into_bytes()))
+}
+
+/// Returns the names and the line indices of the begin and end markers of the regions.
+fn find_regions(lines: &[&str]) -> Vec<(String, usize, usize)> {
+    let mut regions = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        if let Some(name) = marker_name(lines[i], BEGIN_MARKER) {
+            if let Some(end) =
+                (i + 1..lines.len()).find(|&j| marker_name(lines[j], END_MARKER) == Some(name))
+            {
+                regions.push((name.to_string(), i, end));
+                i = end;
+            }
+        }
+        i += 1;
+    }
+    regions
+}
+
+fn marker_name<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+    let (_, rest) = line.split_once(marker)?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    rest.split_whitespace().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f(existing: &str, rendered: &str) -> Result<Option<String>, Error> {
+        let output_file = OutputFile::new("lib.rs", rendered);
+        Ok(apply_regions(existing.as_bytes(), &output_file)?
+            .map(|content| String::from_utf8(content).unwrap()))
+    }
+
+    #[test]
+    fn test_apply_regions() -> Result<(), Error> {
+        assert_eq!(f("a\n", "b\n")?, None);
+        assert_eq!(
+            f(
+                "// hand-written\n// tempura:begin mods\nmod a;\n// tempura:end mods\nfn main() {}\n",
+                "// tempura:begin mods\nmod a;\nmod b;\n// tempura:end mods\n"
+            )?,
+            Some(
+                "// hand-written\n// tempura:begin mods\nmod a;\nmod b;\n// tempura:end mods\nfn main() {}\n"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            f(
+                "<!-- tempura:begin a -->\nx\n<!-- tempura:end a -->\nkeep\n<!-- tempura:begin b -->\n<!-- tempura:end b -->\n<!-- tempura:begin c -->\nc\n<!-- tempura:end c -->\n",
+                "ignored\n<!-- tempura:begin b -->\nB\n<!-- tempura:end b -->\n<!-- tempura:begin a -->\nA\n<!-- tempura:end a -->\n"
+            )?,
+            Some(
+                "<!-- tempura:begin a -->\nA\n<!-- tempura:end a -->\nkeep\n<!-- tempura:begin b -->\nB\n<!-- tempura:end b -->\n<!-- tempura:begin c -->\nc\n<!-- tempura:end c -->\n"
+                    .to_string()
+            )
+        );
+        assert!(matches!(
+            f("a\n", "# tempura:begin x\n# tempura:end x\n"),
+            Err(Error::RegionNotFound(_, name)) if name == "x"
+        ));
+        // not closed
+        assert_eq!(f("a\n", "# tempura:begin x\n")?, None);
+        Ok(())
+    }
+}
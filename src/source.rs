@@ -0,0 +1,214 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::Error;
+
+/// An entry of a template source directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    /// The path relative to the template root.
+    pub path: PathBuf,
+    pub is_dir: bool,
+}
+
+/// Where the template files are read from.
+///
+/// All paths are relative to the template root.
+pub trait Source: std::fmt::Debug {
+    /// Returns the entries in `dir` sorted by path.
+    fn read_dir(&self, dir: &Path) -> Result<Vec<Entry>, Error>;
+
+    /// Returns the content of the file at `path`, or `None` if it is not a file.
+    fn read_file(&self, path: &Path) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Returns the Unix permission bits of the file at `path`, if known.
+    fn mode(&self, _path: &Path) -> Option<u32> {
+        None
+    }
+
+    /// Returns the commit id the templates were read from, if they come from a git repository.
+    fn commit(&self) -> Option<&str> {
+        None
+    }
+
+    /// Returns the directory on the file system (used to resolve `extends` in the manifest).
+    fn dir(&self) -> Option<&Path> {
+        None
+    }
+}
+
+/// Reads the template files from a directory.
+#[derive(Debug)]
+pub struct DirSource {
+    dir: PathBuf,
+}
+
+impl DirSource {
+    pub fn open(dir: &Path) -> Result<Self, Error> {
+        let dir = dir.canonicalize().map_err(|_| Error::TemplateNotFound)?;
+        if !dir.is_dir() {
+            return Err(Error::TemplateIsNotDirectory);
+        }
+        Ok(Self { dir })
+    }
+}
+
+impl Source for DirSource {
+    fn read_dir(&self, dir: &Path) -> Result<Vec<Entry>, Error> {
+        let dir = self.dir.join(dir);
+        let mut entries = dir
+            .read_dir()
+            .and_then(|read_dir| {
+                read_dir
+                    .map(|dir_entry_result| dir_entry_result.map(|dir_entry| dir_entry.path()))
+                    .collect::<std::io::Result<Vec<PathBuf>>>()
+            })
+            .map_err(|_| Error::ReadDirectoryFailed(dir.display().to_string()))?
+            .into_iter()
+            .map(|path| Entry {
+                is_dir: path.is_dir(),
+                path: path
+                    .strip_prefix(self.dir.as_path())
+                    .expect("path to be in dir")
+                    .to_path_buf(),
+            })
+            .collect::<Vec<Entry>>();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(entries)
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Option<Vec<u8>>, Error> {
+        let file = self.dir.join(path);
+        if !file.is_file() {
+            return Ok(None);
+        }
+        std::fs::read(file.as_path())
+            .map(Some)
+            .map_err(|_| Error::ReadFileFailed(file.display().to_string()))
+    }
+
+    #[cfg(unix)]
+    fn mode(&self, path: &Path) -> Option<u32> {
+        use std::os::unix::fs::PermissionsExt;
+        let metadata = std::fs::metadata(self.dir.join(path)).ok()?;
+        Some(metadata.permissions().mode() & 0o7777)
+    }
+
+    fn dir(&self) -> Option<&Path> {
+        Some(self.dir.as_path())
+    }
+}
+
+/// Keeps the template files in memory as a map from the paths to the contents.
+#[derive(Debug, Default)]
+pub struct MemorySource {
+    pub files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl Source for MemorySource {
+    fn read_dir(&self, dir: &Path) -> Result<Vec<Entry>, Error> {
+        Ok(list_dir(self.files.keys().map(PathBuf::as_path), dir))
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.files.get(path).cloned())
+    }
+}
+
+/// Reads the template files from data compiled into the binary.
+///
+/// ```
+/// # fn main() -> Result<(), tempura::Error> {
+/// // e.g. `("{{name}}.txt", include_bytes!("../tmpl/{{name}}.txt"))`
+/// static FILES: &[(&str, &[u8])] = &[("{{name}}.txt", b"Hello,{{name}}")];
+/// let source = tempura::EmbeddedSource::new(FILES);
+/// let template_dir = tempura::TemplateDir::from_sources(vec![Box::new(source)], None)?;
+/// let data = [("name".to_string(), "World".to_string())].into_iter().collect();
+/// let files = template_dir.render(&data)?.into_map();
+/// assert_eq!(files[std::path::Path::new("World.txt")], b"Hello,World");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct EmbeddedSource {
+    files: &'static [(&'static str, &'static [u8])],
+}
+
+impl EmbeddedSource {
+    /// `files` are the pairs of the `/`-separated paths and the contents.
+    pub const fn new(files: &'static [(&'static str, &'static [u8])]) -> Self {
+        Self { files }
+    }
+}
+
+impl Source for EmbeddedSource {
+    fn read_dir(&self, dir: &Path) -> Result<Vec<Entry>, Error> {
+        Ok(list_dir(
+            self.files.iter().map(|(path, _)| Path::new(path)),
+            dir,
+        ))
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self
+            .files
+            .iter()
+            .find(|(file, _)| Path::new(file) == path)
+            .map(|(_, content)| content.to_vec()))
+    }
+}
+
+/// Lists the entries in `dir` from the paths of all files.
+fn list_dir<'a>(files: impl Iterator<Item = &'a Path>, dir: &Path) -> Vec<Entry> {
+    let mut entries = BTreeMap::new();
+    for file in files {
+        let Ok(relative) = file.strip_prefix(dir) else {
+            continue;
+        };
+        let mut components = relative.components();
+        let Some(name) = components.next() else {
+            continue;
+        };
+        let is_dir = components.next().is_some();
+        *entries.entry(dir.join(name)).or_insert(false) |= is_dir;
+    }
+    entries
+        .into_iter()
+        .map(|(path, is_dir)| Entry { path, is_dir })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_source() -> Result<(), Error> {
+        let source = MemorySource {
+            files: [
+                (PathBuf::from("a/b.txt"), b"b".to_vec()),
+                (PathBuf::from("a/c/d.txt"), b"d".to_vec()),
+                (PathBuf::from("e.txt"), b"e".to_vec()),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let entry = |path: &str, is_dir: bool| Entry {
+            path: PathBuf::from(path),
+            is_dir,
+        };
+        assert_eq!(
+            source.read_dir(Path::new(""))?,
+            vec![entry("a", true), entry("e.txt", false)]
+        );
+        assert_eq!(
+            source.read_dir(Path::new("a"))?,
+            vec![entry("a/b.txt", false), entry("a/c", true)]
+        );
+        assert_eq!(source.read_file(Path::new("a/b.txt"))?, Some(b"b".to_vec()));
+        assert_eq!(source.read_file(Path::new("a"))?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,941 @@
+use std::path::{Path, PathBuf};
+
+use crate::{source::Source, Data, Error};
+
+/// A parsed template string.
+#[derive(Debug)]
+pub struct Template {
+    tokens: Vec<Token>,
+    delimiters: Delimiters,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Template {
+    /// Parses `s` with the default delimiters (`{{` and `}}`).
+    pub fn parse(s: &str) -> Self {
+        Self::parse_with_delimiters(s, &Delimiters::default())
+    }
+
+    pub fn parse_with_delimiters(s: &str, delimiters: &Delimiters) -> Self {
+        let (tokens, diagnostics) = parse_tmpl(s, delimiters);
+        Self {
+            tokens,
+            delimiters: delimiters.clone(),
+            diagnostics,
+        }
+    }
+
+    /// Returns the malformed tags that look like intended tags.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Renders the template with `data`. Partials cannot be included.
+    pub fn render(&self, data: &Data) -> Result<String, Error> {
+        self.render_with_partials(data, &Partials::default())
+    }
+
+    pub(crate) fn render_with_partials(
+        &self,
+        data: &Data,
+        partials: &Partials,
+    ) -> Result<String, Error> {
+        render_tokens(&self.tokens, data, &self.delimiters, partials, &mut vec![])
+    }
+
+    /// Renders the template of a file or directory name with `data` into the path segments. Only
+    /// the path filter (e.g. `{{package|path}}`) separates the segments, so the segments may
+    /// contain path separators from the other variables.
+    pub(crate) fn render_path_segments(
+        &self,
+        data: &Data,
+        partials: &Partials,
+    ) -> Result<Vec<String>, Error> {
+        let mut segments = vec![String::new()];
+        for token in self.tokens.iter() {
+            let rendered = match token {
+                Token::PathVar(var) => path_segments(var, data)?,
+                _ => vec![render_tokens(
+                    std::slice::from_ref(token),
+                    data,
+                    &self.delimiters,
+                    partials,
+                    &mut vec![],
+                )?],
+            };
+            let mut rendered = rendered.into_iter();
+            if let (Some(last), Some(first)) = (segments.last_mut(), rendered.next()) {
+                last.push_str(first.as_str());
+            }
+            segments.extend(rendered);
+        }
+        Ok(segments)
+    }
+}
+
+/// Splits the value of `var` into path segments at `/` (and `\`). Returns an error if the value is
+/// absolute (including a drive prefix such as `C:`) or has empty, `.` or `..` segments.
+fn path_segments(var: &str, data: &Data) -> Result<Vec<String>, Error> {
+    let value = data
+        .get(var)
+        .ok_or_else(|| Error::VariableNotFound(var.to_string()))?;
+    let segments = value
+        .split(['/', '\\'])
+        .map(str::to_string)
+        .collect::<Vec<String>>();
+    if segments.iter().any(|segment| {
+        segment.is_empty() || segment == "." || segment == ".." || segment.contains(':')
+    }) {
+        return Err(Error::VariableIsNotValidPath(
+            var.to_string(),
+            value.to_string(),
+        ));
+    }
+    Ok(segments)
+}
+
+/// Renders the template string `tmpl` with `data`.
+pub fn render(tmpl: &str, data: &Data) -> Result<String, Error> {
+    Template::parse(tmpl).render(data)
+}
+
+fn render_tokens(
+    tokens: &[Token],
+    data: &Data,
+    delimiters: &Delimiters,
+    partials: &Partials,
+    includes: &mut Vec<String>,
+) -> Result<String, Error> {
+    tokens.iter().try_fold(String::new(), |acc, token| {
+        Ok(match token {
+            Token::Val(val) => acc + val,
+            Token::Var(var) => {
+                acc + data
+                    .get(var)
+                    .ok_or_else(|| Error::VariableNotFound(var.clone()))?
+            }
+            Token::PathVar(var) => acc + path_segments(var, data)?.join("/").as_str(),
+            Token::Comment(_) => acc,
+            Token::Include(name) => {
+                if includes.contains(name) {
+                    includes.push(name.clone());
+                    return Err(Error::IncludeCycle(includes.join(" -> ")));
+                }
+                let partial = partials.load(name.as_str())?;
+                let (delimiters, partial) = match strip_delimiters_comment(partial.as_str())? {
+                    Some((delimiters, partial)) => (delimiters, partial),
+                    None => (delimiters.clone(), partial.as_str()),
+                };
+                let (tokens, _) = parse_tmpl(partial, &delimiters);
+                includes.push(name.clone());
+                let rendered = render_tokens(&tokens, data, &delimiters, partials, includes)?;
+                includes.pop();
+                acc + &rendered
+            }
+        })
+    })
+}
+
+/// The partials directories of the layers.
+#[derive(Debug, Default)]
+pub(crate) struct Partials<'a> {
+    pub(crate) dirs: Vec<(&'a dyn Source, PathBuf)>,
+}
+
+impl Partials<'_> {
+    fn load(&self, name: &str) -> Result<String, Error> {
+        let path = Path::new(name);
+        if !path
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)))
+        {
+            return Err(Error::PartialNotFound(name.to_string()));
+        }
+        for (source, dir) in self.dirs.iter().rev() {
+            let file = dir.join(path);
+            if let Some(content) = source.read_file(file.as_path())? {
+                return String::from_utf8(content)
+                    .map_err(|_| Error::ReadFileFailed(file.display().to_string()));
+            }
+        }
+        Err(Error::PartialNotFound(name.to_string()))
+    }
+}
+
+/// The open and close delimiters of tags.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Delimiters {
+    open: String,
+    close: String,
+}
+
+impl Default for Delimiters {
+    fn default() -> Self {
+        Self {
+            open: "{{".to_string(),
+            close: "}}".to_string(),
+        }
+    }
+}
+
+impl Delimiters {
+    pub fn new(open: &str, close: &str) -> Result<Self, Error> {
+        if open.is_empty()
+            || close.is_empty()
+            || open.chars().chain(close.chars()).any(char::is_whitespace)
+        {
+            return Err(Error::InvalidDelimiters(format!("{} {}", open, close)));
+        }
+        Ok(Self {
+            open: open.to_string(),
+            close: close.to_string(),
+        })
+    }
+}
+
+impl std::str::FromStr for Delimiters {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            [open, close] => Self::new(open, close),
+            _ => Err(Error::InvalidDelimiters(s.to_string())),
+        }
+    }
+}
+
+/// Removes the `tempura:delimiters <open> <close>` magic comment line at the beginning of `content`.
+pub(crate) fn strip_delimiters_comment(content: &str) -> Result<Option<(Delimiters, &str)>, Error> {
+    const MAGIC: &str = "tempura:delimiters";
+    let (first_line, rest) = match content.find('\n') {
+        Some(index) => content.split_at(index + 1),
+        None => (content, ""),
+    };
+    let Some(index) = first_line.find(MAGIC) else {
+        return Ok(None);
+    };
+    match first_line[index + MAGIC.len()..]
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .as_slice()
+    {
+        [open, close, ..] => Ok(Some((Delimiters::new(open, close)?, rest))),
+        _ => Err(Error::InvalidDelimiters(first_line.trim().to_string())),
+    }
+}
+
+/// The path templates and the contents of file blocks.
+type FileBlocks<'a> = Vec<(&'a str, &'a str)>;
+
+/// The index, the left trim flag, the path template, the right trim flag and the rest of a file
+/// block open tag.
+type FileBlockOpen<'a> = (usize, bool, &'a str, bool, &'a str);
+
+/// Splits the file blocks (e.g. `{{#file "src/{{name}}_test.rs"}}` ... `{{/file}}`) from
+/// `content`. Returns the content outside the blocks and the path templates and the contents of
+/// the blocks. A newline right after a block tag is removed, and the whitespace control markers
+/// (e.g. `{{- #file "a" -}}`) trim the whitespace around the tags.
+pub(crate) fn split_file_blocks<'a>(
+    content: &'a str,
+    delimiters: &Delimiters,
+) -> Result<(String, FileBlocks<'a>), Error> {
+    let trim_after = |s: &'a str, trim: bool| match trim {
+        true => s.trim_start(),
+        false => s.strip_prefix('\n').unwrap_or(s),
+    };
+    let mut main = String::new();
+    let mut blocks = vec![];
+    let mut rest = content;
+    while let Some((index, trim_left, path, trim_right, after)) =
+        find_file_block_open(rest, delimiters)?
+    {
+        main.push_str(&rest[..index]);
+        if trim_left {
+            main.truncate(main.trim_end().len());
+        }
+        let (mut body, trim_body_end, trim_right, after) =
+            find_file_block_close(trim_after(after, trim_right), delimiters)
+                .ok_or_else(|| Error::FileBlockIsNotValid(format!("not closed: {}", path)))?;
+        if trim_body_end {
+            body = body.trim_end();
+        }
+        if find_file_block_open(body, delimiters)?.is_some() {
+            return Err(Error::FileBlockIsNotValid(format!("nested: {}", path)));
+        }
+        blocks.push((path, body));
+        rest = trim_after(after, trim_right);
+    }
+    main.push_str(rest);
+    Ok((main, blocks))
+}
+
+/// Finds the tags named `name` (e.g. `#file`) in `s` skipping the other tags (e.g. raw blocks).
+/// Returns their indexes, their left trim flags and the rest after the names.
+fn find_named_tags<'a: 'b, 'b>(
+    s: &'a str,
+    name: &'b str,
+    delimiters: &'b Delimiters,
+) -> impl Iterator<Item = (usize, bool, &'a str)> + 'b {
+    let open = delimiters.open.as_str();
+    let mut skip_to = 0;
+    s.match_indices(open).filter_map(move |(index, _)| {
+        if index < skip_to {
+            return None;
+        }
+        let after_open = &s[index + open.len()..];
+        if let Some((_, rest)) = parse_tmpl_sub(after_open, delimiters) {
+            skip_to = s.len() - rest.len();
+            return None;
+        }
+        let (trim_left, tag) = match after_open.strip_prefix('-') {
+            Some(tag) => (true, tag),
+            None => (false, after_open),
+        };
+        let tag = tag.trim_start().strip_prefix(name)?;
+        // e.g. `{{#files}}` is not a file block
+        if tag.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+            return None;
+        }
+        Some((index, trim_left, tag))
+    })
+}
+
+/// Finds the first file block open tag in `s`.
+fn find_file_block_open<'a>(
+    s: &'a str,
+    delimiters: &Delimiters,
+) -> Result<Option<FileBlockOpen<'a>>, Error> {
+    let Some((index, trim_left, tag)) = find_named_tags(s, "#file", delimiters).next() else {
+        return Ok(None);
+    };
+    let invalid = || {
+        let line = &s[index..];
+        Error::FileBlockIsNotValid(line[..line.find('\n').unwrap_or(line.len())].to_string())
+    };
+    let tag = tag.trim_start().strip_prefix('"').ok_or_else(invalid)?;
+    let end = tag.find('"').ok_or_else(invalid)?;
+    let (trim_right, rest) =
+        parse_tag_end(&tag[end + 1..], &delimiters.close).ok_or_else(invalid)?;
+    Ok(Some((index, trim_left, &tag[..end], trim_right, rest)))
+}
+
+/// Finds the first file block close tag (`{{/file}}`) in `s`. Returns the block body, the trim flags
+/// and the rest.
+fn find_file_block_close<'a>(
+    s: &'a str,
+    delimiters: &Delimiters,
+) -> Option<(&'a str, bool, bool, &'a str)> {
+    find_named_tags(s, "/file", delimiters).find_map(|(index, trim_left, tag)| {
+        let (trim_right, rest) = parse_tag_end(tag, &delimiters.close)?;
+        Some((&s[..index], trim_left, trim_right, rest))
+    })
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Val(String),
+    Var(String),
+    /// A variable expanded into path segments separated by `/`.
+    PathVar(String),
+    Comment(String),
+    Include(String),
+}
+
+/// A malformed tag that looks like an intended tag.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    pub suggestion: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: {} (did you mean `{}`?)",
+            self.line, self.column, self.message, self.suggestion
+        )
+    }
+}
+
+fn parse_tmpl(s: &str, delimiters: &Delimiters) -> (Vec<Token>, Vec<Diagnostic>) {
+    let mut tokens = vec![];
+    let mut diagnostics = vec![];
+    let mut val = String::new();
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        let Some(after_open) = rest.strip_prefix(delimiters.open.as_str()) else {
+            val.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        };
+        match parse_tmpl_sub(after_open, delimiters) {
+            Some((tag, mut after_tag)) => {
+                if tag.trim_left {
+                    val.truncate(val.trim_end().len());
+                }
+                if let Token::Comment(_) = tag.token {
+                    // remove the line if the comment is standalone
+                    let line_start = match val.rfind('\n') {
+                        Some(index) => Some(index + 1),
+                        None if tokens.is_empty() => Some(0),
+                        None => None,
+                    };
+                    let line_end = after_tag
+                        .find('\n')
+                        .map(|index| index + 1)
+                        .unwrap_or(after_tag.len());
+                    if let Some(line_start) = line_start {
+                        if val[line_start..].trim().is_empty()
+                            && after_tag[..line_end].trim().is_empty()
+                        {
+                            val.truncate(line_start);
+                            after_tag = &after_tag[line_end..];
+                        }
+                    }
+                }
+                match tag.token {
+                    Token::Val(v) => val.push_str(v.as_str()),
+                    token => {
+                        if !val.is_empty() {
+                            tokens.push(Token::Val(std::mem::take(&mut val)));
+                        }
+                        tokens.push(token);
+                    }
+                }
+                rest = if tag.trim_right {
+                    after_tag.trim_start()
+                } else {
+                    after_tag
+                };
+            }
+            None => {
+                let before = &s[..s.len() - rest.len()];
+                // e.g. `${{ secrets.TOKEN }}` of GitHub Actions is not an intended tag
+                let diagnostic = if before.ends_with('$') {
+                    None
+                } else {
+                    diagnose_invalid_tag(rest, delimiters)
+                };
+                if let Some((message, suggestion)) = diagnostic {
+                    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+                    diagnostics.push(Diagnostic {
+                        line: before.matches('\n').count() + 1,
+                        column: before[line_start..].chars().count() + 1,
+                        message,
+                        suggestion,
+                    });
+                }
+                let (invalid_tag, after_tag) = split_invalid_tag(rest, delimiters);
+                val.push_str(invalid_tag);
+                rest = after_tag;
+            }
+        }
+    }
+    if !val.is_empty() {
+        tokens.push(Token::Val(val));
+    }
+    (tokens, diagnostics)
+}
+
+/// A tag with the whitespace control markers (e.g. `{{- name -}}`).
+#[derive(Debug)]
+struct Tag {
+    token: Token,
+    trim_left: bool,
+    trim_right: bool,
+}
+
+/// Parses a tag after the open delimiter and returns the tag and the rest.
+///
+/// If the tag is invalid, it returns `None`.
+fn parse_tmpl_sub<'a>(s: &'a str, delimiters: &Delimiters) -> Option<(Tag, &'a str)> {
+    let Delimiters { open, close } = delimiters;
+    let (trim_left, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let s = s.trim_start();
+
+    // comment (e.g. `{{! comment }}`)
+    if let Some(s) = s.strip_prefix('!') {
+        let index = s.find(close.as_str())?;
+        let (comment, trim_right) = match s[..index].strip_suffix('-') {
+            Some(comment) => (comment, true),
+            None => (&s[..index], false),
+        };
+        let token = Token::Comment(comment.to_string());
+        return Some((
+            Tag {
+                token,
+                trim_left,
+                trim_right,
+            },
+            &s[index + close.len()..],
+        ));
+    }
+
+    // include partial (e.g. `{{> license}}`)
+    if let Some(s) = s.strip_prefix('>') {
+        let index = s.find(close.as_str())?;
+        let (name, trim_right) = match s[..index].trim_end().strip_suffix('-') {
+            Some(name) => (name.trim(), true),
+            None => (s[..index].trim(), false),
+        };
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return None;
+        }
+        let token = Token::Include(name.to_string());
+        return Some((
+            Tag {
+                token,
+                trim_left,
+                trim_right,
+            },
+            &s[index + close.len()..],
+        ));
+    }
+
+    // escape open delimiter (e.g. `{{"{{"}}`)
+    if let Some(s) = s.strip_prefix(format!(r#""{}""#, open).as_str()) {
+        let (trim_right, rest) = parse_tag_end(s, close)?;
+        let token = Token::Val(open.clone());
+        return Some((
+            Tag {
+                token,
+                trim_left,
+                trim_right,
+            },
+            rest,
+        ));
+    }
+
+    // raw block (e.g. `{{#raw}}{{name}}{{/raw}}`)
+    if let Some(s) = s.strip_prefix("#raw") {
+        let (trim_body_start, body) = parse_tag_end(s, close)?;
+        let (index, trim_body_end, trim_right, rest) =
+            body.match_indices(open.as_str()).find_map(|(index, _)| {
+                let s = &body[index + open.len()..];
+                let (trim_body_end, s) = match s.strip_prefix('-') {
+                    Some(s) => (true, s),
+                    None => (false, s),
+                };
+                let s = s.trim_start().strip_prefix("/raw")?;
+                let (trim_right, rest) = parse_tag_end(s, close)?;
+                Some((index, trim_body_end, trim_right, rest))
+            })?;
+        let mut content = &body[..index];
+        if trim_body_start {
+            content = content.trim_start();
+        }
+        if trim_body_end {
+            content = content.trim_end();
+        }
+        let token = Token::Val(content.to_string());
+        return Some((
+            Tag {
+                token,
+                trim_left,
+                trim_right,
+            },
+            rest,
+        ));
+    }
+
+    let var_len = s
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(s.len());
+    if var_len == 0 {
+        return None;
+    }
+    let (var, s) = s.split_at(var_len);
+    // path filter (e.g. `{{package|path}}`)
+    let (token, s) = match s.trim_start().strip_prefix('|') {
+        Some(s) => (
+            Token::PathVar(var.to_string()),
+            s.trim_start().strip_prefix("path")?,
+        ),
+        None => (Token::Var(var.to_string()), s),
+    };
+    let (trim_right, rest) = parse_tag_end(s, close)?;
+    Some((
+        Tag {
+            token,
+            trim_left,
+            trim_right,
+        },
+        rest,
+    ))
+}
+
+/// Parses the end of a tag (e.g. ` -}}`) and returns the right trim marker and the rest.
+fn parse_tag_end<'a>(s: &'a str, close: &str) -> Option<(bool, &'a str)> {
+    let s = s.trim_start();
+    if let Some(rest) = s.strip_prefix(close) {
+        return Some((false, rest));
+    }
+    s.strip_prefix('-')
+        .and_then(|s| s.strip_prefix(close))
+        .map(|rest| (true, rest))
+}
+
+/// Returns the message and the suggestion if an invalid tag looks like an intended tag.
+///
+/// An invalid tag looks like an intended tag if the (partial) close delimiter follows it on the same line.
+fn diagnose_invalid_tag(s: &str, delimiters: &Delimiters) -> Option<(String, String)> {
+    let Delimiters { open, close } = delimiters;
+    let after_open = &s[open.len()..];
+    let line = &after_open[..after_open.find('\n').unwrap_or(after_open.len())];
+    let line = &line[..line.find(open.as_str()).unwrap_or(line.len())];
+    let is_var_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
+
+    if let Some(index) = line.find(close.as_str()) {
+        let name = line[..index]
+            .trim()
+            .trim_start_matches('-')
+            .trim_end_matches('-')
+            .trim();
+        // e.g. `{{ github.ref }}` is an expression of another template language
+        if name.is_empty() || name.starts_with('"') || name.starts_with('#') || name.contains('.') {
+            return None;
+        }
+        let fixed = name
+            .split(|c: char| !is_var_char(c))
+            .filter(|it| !it.is_empty())
+            .collect::<Vec<&str>>()
+            .join("_");
+        if fixed.is_empty() {
+            return None;
+        }
+        return Some((
+            format!("invalid variable name `{}`", name),
+            format!("{}{}{}", open, fixed, close),
+        ));
+    }
+
+    let name = line.trim_start();
+    let name = &name[..name.find(|c: char| !is_var_char(c)).unwrap_or(name.len())];
+    let after_name = line.trim_start()[name.len()..].trim_start();
+    let partial_close = (1..close.len())
+        .filter(|i| close.is_char_boundary(*i))
+        .any(|i| after_name.starts_with(&close[..i]));
+    if !name.is_empty() && partial_close {
+        return Some((
+            "unclosed tag".to_string(),
+            format!("{}{}{}", open, name, close),
+        ));
+    }
+    None
+}
+
+/// Splits an invalid tag into the string output as is and the rest.
+///
+/// The string output as is consists of the open delimiter, the following variable name characters and
+/// the next character (if it does not start another open delimiter).
+fn split_invalid_tag<'a>(s: &'a str, delimiters: &Delimiters) -> (&'a str, &'a str) {
+    let open = delimiters.open.as_str();
+    let after_open = &s[open.len()..];
+    let var_len = after_open
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(after_open.len());
+    let rest = &after_open[var_len..];
+    let len = match rest.chars().next() {
+        Some(c) if !rest.starts_with(open) => open.len() + var_len + c.len_utf8(),
+        _ => open.len() + var_len,
+    };
+    s.split_at(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tmpl() {
+        use Token::*;
+        let f = |s: &str| Template::parse(s).tokens;
+        let l = |s: &str| -> Token { Val(s.to_string()) };
+        let r = |s: &str| -> Token { Var(s.to_string()) };
+        assert_eq!(f(""), vec![]);
+        assert_eq!(
+            f("ab{{cd}}ef{{gh}}"),
+            vec![l("ab"), r("cd"), l("ef"), r("gh")]
+        );
+        assert_eq!(f("a"), vec![l("a")]);
+        assert_eq!(f("a{"), vec![l("a{")]);
+        assert_eq!(f("a{b"), vec![l("a{b")]);
+        assert_eq!(f("a{{"), vec![l("a{{")]);
+        assert_eq!(f("a{{b"), vec![l("a{{b")]);
+        assert_eq!(f("a{{b}"), vec![l("a{{b}")]);
+        assert_eq!(f("a{{b}c"), vec![l("a{{b}c")]);
+        assert_eq!(f("a{{b}}"), vec![l("a"), r("b")]);
+        assert_eq!(f("a{{b}}c"), vec![l("a"), r("b"), l("c")]);
+        assert_eq!(f("{{a}}"), vec![r("a")]);
+        assert_eq!(f("{{a}}b"), vec![r("a"), l("b")]);
+        assert_eq!(f("{{a}}{"), vec![r("a"), l("{")]);
+        assert_eq!(f("{{a}}{b"), vec![r("a"), l("{b")]);
+        assert_eq!(f("{{a}}{{"), vec![r("a"), l("{{")]);
+        assert_eq!(f("{{a}}{{b"), vec![r("a"), l("{{b")]);
+        assert_eq!(f("{{a}}{{b}"), vec![r("a"), l("{{b}")]);
+        assert_eq!(f("{{a}}{{b}c"), vec![r("a"), l("{{b}c")]);
+        assert_eq!(f("{{a}}{{b}}"), vec![r("a"), r("b")]);
+        assert_eq!(f("{{a}}{{b}}c"), vec![r("a"), r("b"), l("c")]);
+        // escape {{
+        assert_eq!(f(r#"{{""#), vec![l(r#"{{""#)]);
+        assert_eq!(f(r#"{{"{"#), vec![l(r#"{{"{"#)]);
+        assert_eq!(f(r#"{{"{{"#), vec![l(r#"{{"{{"#)]);
+        assert_eq!(f(r#"{{"{{""#), vec![l(r#"{{"{{""#)]);
+        assert_eq!(f(r#"{{"{{"}"#), vec![l(r#"{{"{{"}"#)]);
+        assert_eq!(f(r#"{{"{{"}}"#), vec![l(r#"{{"#)]);
+        assert_eq!(f(r#"{{a"{{"}}"#), vec![l(r#"{{a"{{"}}"#)]);
+        // space is allowed
+        assert_eq!(f(r#"{{ a }}"#), vec![r("a")]);
+        assert_eq!(f(r#"{{ "{{" }}"#), vec![l("{{")]);
+        assert_eq!(f("{{\ta\n}}"), vec![r("a")]);
+        assert_eq!(f("{{ a b }}"), vec![l("{{ a b }}")]);
+        assert_eq!(f("{{ }}"), vec![l("{{ }}")]);
+        // whitespace control
+        assert_eq!(f("a \n{{- b -}}\n c"), vec![l("a"), r("b"), l("c")]);
+        assert_eq!(f("a \n{{-b}}\n c"), vec![l("a"), r("b"), l("\n c")]);
+        assert_eq!(f("a \n{{b-}}\n c"), vec![l("a \n"), r("b"), l("c")]);
+        assert_eq!(f("{{a}} {{- b}}"), vec![r("a"), r("b")]);
+        assert_eq!(f("a {{- \"{{\" -}} b"), vec![l("a{{b")]);
+        assert_eq!(f("{{-}}"), vec![l("{{-}}")]);
+        assert_eq!(f("{{- a -}"), vec![l("{{- a -}")]);
+        // raw block
+        assert_eq!(f("{{#raw}}{{a}}{{/raw}}"), vec![l("{{a}}")]);
+        assert_eq!(f("a{{#raw}}{{b}}{{/raw}}{{c}}"), vec![l("a{{b}}"), r("c")]);
+        assert_eq!(f("{{#raw}}{{#raw}}{{/raw}}"), vec![l("{{#raw}}")]);
+        assert_eq!(f("{{#raw}}{{a}}"), vec![l("{{#raw}}"), r("a")]);
+        assert_eq!(f("{{ #raw }}{{a}}{{ /raw }}"), vec![l("{{a}}")]);
+        assert_eq!(
+            f("a {{- #raw -}}\n {{b}} \n{{- /raw -}} c"),
+            vec![l("a{{b}}c")]
+        );
+    }
+
+    #[test]
+    fn test_parse_tmpl_with_delimiters() -> Result<(), Error> {
+        use Token::*;
+        let delimiters = "<% %>".parse::<Delimiters>()?;
+        let f = |s: &str| Template::parse_with_delimiters(s, &delimiters).tokens;
+        let l = |s: &str| -> Token { Val(s.to_string()) };
+        let r = |s: &str| -> Token { Var(s.to_string()) };
+        assert_eq!(f("a<%b%>c"), vec![l("a"), r("b"), l("c")]);
+        assert_eq!(f("a{{b}}c"), vec![l("a{{b}}c")]);
+        assert_eq!(f("a<%b%"), vec![l("a<%b%")]);
+        assert_eq!(f("a<%b<%c%>"), vec![l("a<%b"), r("c")]);
+        assert_eq!(f(r#"<%"<%"%>"#), vec![l("<%")]);
+        assert_eq!(f(r#"${{ <%a%> }}"#), vec![l("${{ "), r("a"), l(" }}")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_tmpl_comment() {
+        use Token::*;
+        let f = |s: &str| Template::parse(s).tokens;
+        let l = |s: &str| -> Token { Val(s.to_string()) };
+        let r = |s: &str| -> Token { Var(s.to_string()) };
+        let c = |s: &str| -> Token { Comment(s.to_string()) };
+        assert_eq!(f("{{!a}}"), vec![c("a")]);
+        assert_eq!(f("a{{! b }}c"), vec![l("a"), c(" b "), l("c")]);
+        assert_eq!(f("a{{! b\nc }}d"), vec![l("a"), c(" b\nc "), l("d")]);
+        assert_eq!(f("a {{- ! b -}} c"), vec![l("a"), c(" b "), l("c")]);
+        assert_eq!(f("{{! a"), vec![l("{{! a")]);
+        // standalone
+        assert_eq!(f("a\n  {{! b }}  \nc"), vec![l("a\n"), c(" b "), l("c")]);
+        assert_eq!(f("{{! a }}\nb"), vec![c(" a "), l("b")]);
+        assert_eq!(f("a\n{{! b }}"), vec![l("a\n"), c(" b ")]);
+        assert_eq!(f("a\n{{! b\nc }}\nd"), vec![l("a\n"), c(" b\nc "), l("d")]);
+        assert_eq!(f("a {{! b }}\nc"), vec![l("a "), c(" b "), l("\nc")]);
+        assert_eq!(f("{{a}}{{! b }}\nc"), vec![r("a"), c(" b "), l("\nc")]);
+    }
+
+    #[test]
+    fn test_parse_tmpl_include() {
+        use Token::*;
+        let f = |s: &str| Template::parse(s).tokens;
+        let l = |s: &str| -> Token { Val(s.to_string()) };
+        let i = |s: &str| -> Token { Include(s.to_string()) };
+        assert_eq!(f("{{>a}}"), vec![i("a")]);
+        assert_eq!(f("a{{> b/c.txt }}d"), vec![l("a"), i("b/c.txt"), l("d")]);
+        assert_eq!(f("a {{- > b -}} c"), vec![l("a"), i("b"), l("c")]);
+        assert_eq!(f("{{> }}"), vec![l("{{> }}")]);
+        assert_eq!(f("{{> a b }}"), vec![l("{{> a b }}")]);
+    }
+
+    #[test]
+    fn test_template_render() -> Result<(), Error> {
+        let data = [("name".to_string(), "World".to_string())]
+            .into_iter()
+            .collect::<Data>();
+        assert_eq!(
+            render("Hello,{{ name }}{{! comment }}", &data)?,
+            "Hello,World"
+        );
+        assert_eq!(
+            Template::parse_with_delimiters("Hello,<%name%>", &"<% %>".parse()?).render(&data)?,
+            "Hello,World"
+        );
+        assert!(matches!(
+            render("{{name1}}", &data),
+            Err(Error::VariableNotFound(name)) if name == "name1"
+        ));
+        assert!(matches!(
+            render("{{> license}}", &data),
+            Err(Error::PartialNotFound(name)) if name == "license"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_template_render_path() -> Result<(), Error> {
+        let data = [
+            ("package".to_string(), "com/example/app".to_string()),
+            ("name".to_string(), "a/b".to_string()),
+            ("root".to_string(), "/etc".to_string()),
+            ("parent".to_string(), "com/../app".to_string()),
+            ("empty".to_string(), "com//app".to_string()),
+        ]
+        .into_iter()
+        .collect::<Data>();
+        let partials = Partials::default();
+        assert_eq!(
+            render("src/{{ package | path }}/Main.java", &data)?,
+            "src/com/example/app/Main.java"
+        );
+        assert_eq!(
+            Template::parse("x{{package|path}}{{name}}").render_path_segments(&data, &partials)?,
+            vec!["xcom", "example", "appa/b"]
+        );
+        assert_eq!(
+            Template::parse("{{name}}").render_path_segments(&data, &partials)?,
+            vec!["a/b"]
+        );
+        for var in ["root", "parent", "empty"] {
+            assert!(matches!(
+                render(format!("{{{{{}|path}}}}", var).as_str(), &data),
+                Err(Error::VariableIsNotValidPath(name, _)) if name == var
+            ));
+        }
+        assert_eq!(render("{{package|upper}}", &data)?, "{{package|upper}}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_tmpl() {
+        let f = |s: &str| {
+            Template::parse(s)
+                .diagnostics
+                .into_iter()
+                .map(|it| it.to_string())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(f("{{a}} {{ a }} {{- a -}}"), Vec::<String>::new());
+        assert_eq!(f("a{{ a{{b {{"), Vec::<String>::new());
+        assert_eq!(f(r#"{{}} {{"{{"}} {{"{{" }}"#), Vec::<String>::new());
+        assert_eq!(
+            f("a\nb {{inv@lid}}"),
+            vec!["2:3: invalid variable name `inv@lid` (did you mean `{{inv_lid}}`?)"]
+        );
+        assert_eq!(f("${{ secrets.TOKEN }}"), Vec::<String>::new());
+        assert_eq!(f("${{ inv@lid }} {{ github.ref }}"), Vec::<String>::new());
+        assert_eq!(
+            f("${{ a }} {{inv@lid}}"),
+            vec!["1:10: invalid variable name `inv@lid` (did you mean `{{inv_lid}}`?)"]
+        );
+        assert_eq!(
+            f("{{name} {{ name }"),
+            vec![
+                "1:1: unclosed tag (did you mean `{{name}}`?)",
+                "1:9: unclosed tag (did you mean `{{name}}`?)",
+            ]
+        );
+        assert_eq!(f("{{name\n}}"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_delimiters_from_str() {
+        assert_eq!(
+            "[[ ]]".parse::<Delimiters>().ok(),
+            Some(Delimiters::new("[[", "]]").unwrap())
+        );
+        assert!("[[".parse::<Delimiters>().is_err());
+        assert!("[[ ]] ]]".parse::<Delimiters>().is_err());
+        assert!(Delimiters::new("", "]]").is_err());
+    }
+
+    #[test]
+    fn test_strip_delimiters_comment() -> Result<(), Error> {
+        let f = strip_delimiters_comment;
+        assert_eq!(f("a\nb")?, None);
+        assert_eq!(
+            f("# tempura:delimiters <% %>\na<%b%>")?,
+            Some((Delimiters::new("<%", "%>")?, "a<%b%>"))
+        );
+        assert_eq!(
+            f("<!-- tempura:delimiters [[ ]] -->\n")?,
+            Some((Delimiters::new("[[", "]]")?, ""))
+        );
+        assert!(f("# tempura:delimiters <%\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_file_blocks() -> Result<(), Error> {
+        let f = |s| split_file_blocks(s, &Delimiters::default());
+        assert_eq!(f("a{{b}}\n")?, ("a{{b}}\n".to_string(), vec![]));
+        assert_eq!(
+            f("fn {{name}}() {}\n{{#file \"src/{{name}}_test.rs\"}}\n#[test]\nfn t() {}\n{{/file}}\n")?,
+            (
+                "fn {{name}}() {}\n".to_string(),
+                vec![("src/{{name}}_test.rs", "#[test]\nfn t() {}\n")]
+            )
+        );
+        assert_eq!(
+            f("{{ #file \"a\" -}}1{{/file}}{{#file \"b\"}}2{{ /file }}")?,
+            ("".to_string(), vec![("a", "1"), ("b", "2")])
+        );
+        assert_eq!(
+            split_file_blocks("<%#file \"a\"%>1<%/file%>", &Delimiters::new("<%", "%>")?)?,
+            ("".to_string(), vec![("a", "1")])
+        );
+        assert_eq!(
+            f("{{#files}}a{{/files}}{{#filter}}{{#file_name}}")?,
+            (
+                "{{#files}}a{{/files}}{{#filter}}{{#file_name}}".to_string(),
+                vec![]
+            )
+        );
+        assert_eq!(
+            f("Docs: {{#raw}}{{#file \"x.txt\"}}body{{/file}}{{/raw}}")?,
+            (
+                "Docs: {{#raw}}{{#file \"x.txt\"}}body{{/file}}{{/raw}}".to_string(),
+                vec![]
+            )
+        );
+        assert_eq!(
+            f("{{#file \"a\"}}{{#raw}}{{/file}}{{/raw}}{{/file}}")?,
+            ("".to_string(), vec![("a", "{{#raw}}{{/file}}{{/raw}}")])
+        );
+        assert_eq!(
+            f("a \n{{- #file \"b\" -}}\n 1 \n{{- /file -}}\n c\n")?,
+            ("ac\n".to_string(), vec![("b", "1")])
+        );
+        assert_eq!(
+            f("{{#file \"a\"}}\n 1 \n{{/file}}\n\nc")?,
+            ("\nc".to_string(), vec![("a", " 1 \n")])
+        );
+        for s in [
+            "{{#file}}1{{/file}}",
+            "{{#file \"a\"}}1",
+            "{{#file a}}1{{/file}}",
+            "{{#file \"a\"}}{{#file \"b\"}}{{/file}}{{/file}}",
+        ] {
+            assert!(matches!(f(s), Err(Error::FileBlockIsNotValid(_))));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    diff::merge3, output::apply_to_existing, ConflictPolicy, Error, FsOutput, Output, OutputFile,
+    Rendered,
+};
+
+/// How a file was updated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UpdateStatus {
+    /// The file was added to the template.
+    Added,
+    /// The file was changed in the template and not modified locally.
+    Updated,
+    /// The changes in the template were merged with the local modifications.
+    Merged,
+    /// The changes in the template conflicted with the local modifications. The conflict markers
+    /// were written to text files, binary files were left as is.
+    Conflicted,
+    /// The file was removed from the template and not modified locally.
+    Removed,
+    /// The file was changed or removed in the template but deleted or modified locally, so it was
+    /// left as is.
+    Skipped,
+}
+
+impl std::fmt::Display for UpdateStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Added => "added",
+            Self::Updated => "updated",
+            Self::Merged => "merged",
+            Self::Conflicted => "conflicted",
+            Self::Removed => "removed",
+            Self::Skipped => "skipped",
+        })
+    }
+}
+
+/// Applies the changes between the files rendered from the old templates (`base`) and the new
+/// templates (`new`) to the files in `output_dir` with three-way merge.
+///
+/// The injected snippets, the merged documents and the managed regions are applied to the existing
+/// files instead (an injected snippet of `base` is replaced with the snippet of `new`). The other
+/// existing files are kept or overwritten without merging if their conflict policy says so.
+///
+/// Returns the changed files (the files that are unchanged in the template or already up to date
+/// are omitted).
+pub fn update(
+    output_dir: &Path,
+    base: &Rendered,
+    new: &Rendered,
+) -> Result<Vec<(PathBuf, UpdateStatus)>, Error> {
+    let (base, new) = (to_map(base), to_map(new));
+    let mut paths = base
+        .keys()
+        .chain(new.keys())
+        .copied()
+        .collect::<Vec<&Path>>();
+    paths.sort();
+    paths.dedup();
+
+    let mut statuses = vec![];
+    for path in paths {
+        let base = base
+            .get(path)
+            .map(|output_file| output_file.content.as_slice());
+        let new = new.get(path).copied();
+        if base == new.map(|output_file| output_file.content.as_slice()) {
+            continue;
+        }
+        let file = output_dir.join(path);
+        let local = if file.is_file() {
+            Some(
+                std::fs::read(file.as_path())
+                    .map_err(|_| Error::ReadFileFailed(file.display().to_string()))?,
+            )
+        } else {
+            None
+        };
+        let write = |content: &[u8]| {
+            std::fs::write(file.as_path(), content)
+                .map_err(|_| Error::WriteFileFailed(file.display().to_string()))
+        };
+        if let (Some(new), Some(local)) = (new, local.as_ref()) {
+            if let Some(content) = apply_to_existing_with_base(local, base, new)? {
+                if &content != local {
+                    write(content.as_slice())?;
+                    statuses.push((path.to_path_buf(), UpdateStatus::Updated));
+                }
+                continue;
+            }
+            match new.conflict {
+                ConflictPolicy::Skip if &new.content != local => {
+                    statuses.push((path.to_path_buf(), UpdateStatus::Skipped));
+                    continue;
+                }
+                ConflictPolicy::Overwrite if &new.content != local => {
+                    write(new.content.as_slice())?;
+                    statuses.push((path.to_path_buf(), UpdateStatus::Updated));
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        let status = match (new, local) {
+            (Some(new), None) if base.is_none() => {
+                FsOutput::new(output_dir).write_file(new)?;
+                UpdateStatus::Added
+            }
+            (Some(new), Some(local)) if local == new.content => continue,
+            (Some(new), Some(local)) if Some(local.as_slice()) == base => {
+                write(new.content.as_slice())?;
+                UpdateStatus::Updated
+            }
+            (Some(new), Some(local)) => {
+                let texts = (
+                    std::str::from_utf8(base.unwrap_or_default()),
+                    std::str::from_utf8(local.as_slice()),
+                    std::str::from_utf8(new.content.as_slice()),
+                );
+                match texts {
+                    (Ok(base), Ok(local), Ok(new)) => {
+                        let merged = merge3(base, local, new);
+                        write(merged.content.as_bytes())?;
+                        if merged.conflicts == 0 {
+                            UpdateStatus::Merged
+                        } else {
+                            UpdateStatus::Conflicted
+                        }
+                    }
+                    _ => UpdateStatus::Conflicted,
+                }
+            }
+            (None, None) => continue,
+            (None, Some(local)) if Some(local.as_slice()) == base => {
+                std::fs::remove_file(file.as_path())
+                    .map_err(|_| Error::WriteFileFailed(file.display().to_string()))?;
+                UpdateStatus::Removed
+            }
+            (Some(_), None) | (None, Some(_)) => UpdateStatus::Skipped,
+        };
+        statuses.push((path.to_path_buf(), status));
+    }
+    Ok(statuses)
+}
+
+/// Applies `new` to the `local` file like [`FsOutput`] does, but replaces the injected snippet of
+/// `base` first if the local file contains it.
+fn apply_to_existing_with_base(
+    local: &[u8],
+    base: Option<&[u8]>,
+    new: &OutputFile,
+) -> Result<Option<Vec<u8>>, Error> {
+    let snippets = (
+        std::str::from_utf8(local),
+        base.map(std::str::from_utf8),
+        std::str::from_utf8(new.content.as_slice()),
+    );
+    if let (Some(_), Ok(local), Some(Ok(base)), Ok(snippet)) =
+        (&new.inject, snippets.0, snippets.1, snippets.2)
+    {
+        let (base, snippet) = (base.trim(), snippet.trim());
+        if !base.is_empty() && local.contains(base) && !local.contains(snippet) {
+            return Ok(Some(local.replacen(base, snippet, 1).into_bytes()));
+        }
+    }
+    apply_to_existing(local, new)
+}
+
+fn to_map(rendered: &Rendered) -> BTreeMap<&Path, &OutputFile> {
+    rendered
+        .files
+        .iter()
+        .map(|output_file| (output_file.path.as_path(), output_file))
+        .collect()
+}
@@ -0,0 +1,1655 @@
+use std::{fs, io::Write};
+
+use assert_cmd::Command;
+use tempdir::TempDir;
+
+#[test]
+fn test_example_ignore_file() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    // <temp_dir>/tmpl/.tempuraignore
+    // <temp_dir>/tmpl/README.md
+    // <temp_dir>/tmpl/{{name}}.txt
+    // <temp_dir>/tmpl/{{name}}.txt.bak
+    // <temp_dir>/tmpl/fixtures/a.txt
+    // <temp_dir>/tmpl/nested/.tempuraignore
+    // <temp_dir>/tmpl/nested/a.txt
+    // <temp_dir>/tmpl/nested/b.txt
+    // <temp_dir>/tmpl/nested/keep.bak
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.as_path())?;
+    fs::write(
+        tmpl_dir.join(".tempuraignore"),
+        "# comment\nREADME.md\n*.bak\nfixtures/\n",
+    )?;
+    fs::write(tmpl_dir.join("README.md"), r#"for maintainers"#)?;
+    fs::write(tmpl_dir.join("{{name}}.txt"), r#"Hello,{{name}}"#)?;
+    fs::write(tmpl_dir.join("{{name}}.txt.bak"), r#"backup"#)?;
+    let fixtures_dir = tmpl_dir.join("fixtures");
+    fs::create_dir_all(fixtures_dir.as_path())?;
+    fs::write(fixtures_dir.join("a.txt"), r#"fixture"#)?;
+    let nested_dir = tmpl_dir.join("nested");
+    fs::create_dir_all(nested_dir.as_path())?;
+    fs::write(nested_dir.join(".tempuraignore"), "b.txt\n!keep.bak\n")?;
+    fs::write(nested_dir.join("a.txt"), r#"a"#)?;
+    fs::write(nested_dir.join("b.txt"), r#"b"#)?;
+    fs::write(nested_dir.join("keep.bak"), r#"keep"#)?;
+    Command::cargo_bin("tempura")?
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .success();
+    // <temp_dir>/World.txt
+    // <temp_dir>/nested/a.txt
+    // <temp_dir>/nested/keep.bak
+    assert_eq!(
+        fs::read_to_string(temp_dir.join("World.txt"))?,
+        "Hello,World"
+    );
+    assert!(!temp_dir.join(".tempuraignore").exists());
+    assert!(!temp_dir.join("README.md").exists());
+    assert!(!temp_dir.join("World.txt.bak").exists());
+    assert!(!temp_dir.join("fixtures").exists());
+    assert_eq!(
+        fs::read_to_string(temp_dir.join("nested").join("a.txt"))?,
+        "a"
+    );
+    assert!(!temp_dir.join("nested").join(".tempuraignore").exists());
+    assert!(!temp_dir.join("nested").join("b.txt").exists());
+    assert_eq!(
+        fs::read_to_string(temp_dir.join("nested").join("keep.bak"))?,
+        "keep"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_error_invalid_delimiters() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.as_path())?;
+    fs::write(tmpl_dir.join("{{name}}.txt"), r#"Hello,{{name}}"#)?;
+    Command::cargo_bin("tempura")?
+        .arg("--delimiters")
+        .arg("<%")
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .failure()
+        .stderr("Error: InvalidDelimiters(\"<%\")\n");
+    Ok(())
+}
+
+#[test]
+fn test_example_delimiters_option() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    // <temp_dir>/tmpl/<%dir%>/<%name%>.yml
+    let tmpl_dir = temp_dir.join("tmpl");
+    let nested_dir = tmpl_dir.join("<%dir%>");
+    fs::create_dir_all(nested_dir.as_path())?;
+    fs::write(
+        nested_dir.join("<%name%>.yml"),
+        r#"name: <%name%>, token: ${{ secrets.TOKEN }}, escaped: <%"<%"%>"#,
+    )?;
+    Command::cargo_bin("tempura")?
+        .arg("--delimiters")
+        .arg("<% %>")
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"dir":"workflows","name":"ci"}"#)
+        .assert()
+        .success();
+    // <temp_dir>/workflows/ci.yml
+    assert_eq!(
+        fs::read_to_string(temp_dir.join("workflows").join("ci.yml"))?,
+        r#"name: ci, token: ${{ secrets.TOKEN }}, escaped: <%"#
+    );
+    Ok(())
+}
+
+#[test]
+fn test_example_delimiters_manifest() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    // <temp_dir>/tmpl/.tempura.json
+    // <temp_dir>/tmpl/[[name]].txt
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.as_path())?;
+    fs::write(
+        tmpl_dir.join(".tempura.json"),
+        r#"{"delimiters":["[[","]]"]}"#,
+    )?;
+    fs::write(tmpl_dir.join("[[name]].txt"), r#"Hello,[[name]] {{name}}"#)?;
+    Command::cargo_bin("tempura")?
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .success();
+    // <temp_dir>/World.txt
+    assert!(!temp_dir.join(".tempura.json").exists());
+    assert_eq!(
+        fs::read_to_string(temp_dir.join("World.txt"))?,
+        "Hello,World {{name}}"
+    );
+
+    // the option overrides the manifest
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.as_path())?;
+    fs::write(
+        tmpl_dir.join(".tempura.json"),
+        r#"{"delimiters":["[[","]]"]}"#,
+    )?;
+    fs::write(tmpl_dir.join("<%name%>.txt"), r#"Hello,<%name%> [[name]]"#)?;
+    Command::cargo_bin("tempura")?
+        .arg("--delimiters")
+        .arg("<% %>")
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.join("World.txt"))?,
+        "Hello,World [[name]]"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_example_delimiters_magic_comment() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    // <temp_dir>/tmpl/{{name}}.hbs
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.as_path())?;
+    fs::write(
+        tmpl_dir.join("{{name}}.hbs"),
+        "{{! tempura:delimiters <% %> }}\n<p>{{title}} by <%name%></p>\n",
+    )?;
+    Command::cargo_bin("tempura")?
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .success();
+    // <temp_dir>/World.hbs
+    assert_eq!(
+        fs::read_to_string(temp_dir.join("World.hbs"))?,
+        "<p>{{title}} by World</p>\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_example_raw_block() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.as_path())?;
+    fs::write(
+        tmpl_dir.join("{{name}}.yml"),
+        "name: {{name}}\n{{#raw}}token: ${{ secrets.TOKEN }}\n{{/raw}}",
+    )?;
+    Command::cargo_bin("tempura")?
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"ci"}"#)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.join("ci.yml"))?,
+        "name: ci\ntoken: ${{ secrets.TOKEN }}\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_example_raw_file() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    // <temp_dir>/tmpl/.tempura.json
+    // <temp_dir>/tmpl/{{name}}.hbs.raw
+    // <temp_dir>/tmpl/partials/{{name}}.hbs
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.as_path())?;
+    fs::write(
+        tmpl_dir.join(".tempura.json"),
+        r#"{"raw":["partials/*.hbs"]}"#,
+    )?;
+    fs::write(tmpl_dir.join("{{name}}.hbs.raw"), r#"<p>{{name}}</p>"#)?;
+    let partials_dir = tmpl_dir.join("partials");
+    fs::create_dir_all(partials_dir.as_path())?;
+    fs::write(partials_dir.join("{{name}}.hbs"), r#"<b>{{name}}</b>"#)?;
+    Command::cargo_bin("tempura")?
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .success();
+    // <temp_dir>/World.hbs
+    // <temp_dir>/partials/World.hbs
+    assert_eq!(
+        fs::read_to_string(temp_dir.join("World.hbs"))?,
+        "<p>{{name}}</p>"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.join("partials").join("World.hbs"))?,
+        "<b>{{name}}</b>"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_example_whitespace_in_tags() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.as_path())?;
+    fs::write(
+        tmpl_dir.join("{{ name }}.txt"),
+        "Hello, {{ name }}!\n  {{- greet -}}  \n\nBye",
+    )?;
+    Command::cargo_bin("tempura")?
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"greet":"Hi","name":"World"}"#)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.join("World.txt"))?,
+        "Hello, World!HiBye"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_error_malformed_tags() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    // <temp_dir>/tmpl/{{name}.txt
+    // <temp_dir>/tmpl/{{name}}.txt
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.as_path())?;
+    fs::write(tmpl_dir.join("{{name}.txt"), r#"Hello"#)?;
+    fs::write(tmpl_dir.join("{{name}}.txt"), "Hello,\n{{inv@lid}}")?;
+    Command::cargo_bin("tempura")?
+        .arg("--strict")
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .failure()
+        .stderr(concat!(
+            "Error: MalformedTags([",
+            "\"{{name}.txt (name):1:1: unclosed tag (did you mean `{{name}}`?)\", ",
+            "\"{{name}}.txt:2:1: invalid variable name `inv@lid` (did you mean `{{inv_lid}}`?)\"",
+            "])\n"
+        ));
+    assert!(!temp_dir.join("World.txt").exists());
+    Ok(())
+}
+
+#[test]
+fn test_example_malformed_tags_warning() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.as_path())?;
+    fs::write(tmpl_dir.join("{{name}}.txt"), "Hello,{{name}\n")?;
+    Command::cargo_bin("tempura")?
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .success()
+        .stderr("warning: {{name}}.txt:1:7: unclosed tag (did you mean `{{name}}`?)\n");
+    assert_eq!(
+        fs::read_to_string(temp_dir.join("World.txt"))?,
+        "Hello,{{name}\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_example_comment() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.as_path())?;
+    fs::write(
+        tmpl_dir.join("{{name}}{{! comment in name }}.txt"),
+        "{{! explain why\n    this section exists }}\nHello,{{name}}{{! inline }}!\n  {{! standalone }}\nBye\n",
+    )?;
+    Command::cargo_bin("tempura")?
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(temp_dir.join("World.txt"))?,
+        "Hello,World!\nBye\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_error_include_cycle() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    // <temp_dir>/tmpl/_partials/a
+    // <temp_dir>/tmpl/_partials/b
+    // <temp_dir>/tmpl/{{name}}.txt
+    let tmpl_dir = temp_dir.join("tmpl");
+    let partials_dir = tmpl_dir.join("_partials");
+    fs::create_dir_all(partials_dir.as_path())?;
+    fs::write(partials_dir.join("a"), r#"a{{> b}}"#)?;
+    fs::write(partials_dir.join("b"), r#"b{{> a}}"#)?;
+    fs::write(tmpl_dir.join("{{name}}.txt"), r#"{{> a}}"#)?;
+    Command::cargo_bin("tempura")?
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .failure()
+        .stderr("Error: IncludeCycle(\"a -> b -> a\")\n");
+    Ok(())
+}
+
+#[test]
+fn test_error_partial_not_found() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.as_path())?;
+    fs::write(tmpl_dir.join("{{name}}.txt"), r#"{{> license}}"#)?;
+    Command::cargo_bin("tempura")?
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .failure()
+        .stderr("Error: PartialNotFound(\"license\")\n");
+
+    // partials outside the partials directory cannot be included
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.join("_partials"))?;
+    fs::write(tmpl_dir.join("secret"), r#"secret"#)?;
+    fs::write(tmpl_dir.join("{{name}}.txt"), r#"{{> ../secret}}"#)?;
+    Command::cargo_bin("tempura")?
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .failure()
+        .stderr("Error: PartialNotFound(\"../secret\")\n");
+    Ok(())
+}
+
+#[test]
+fn test_example_partials() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    // <temp_dir>/tmpl/_partials/license
+    // <temp_dir>/tmpl/_partials/ci/checkout.yml
+    // <temp_dir>/tmpl/{{name}}.rs
+    // <temp_dir>/tmpl/ci.yml
+    let tmpl_dir = temp_dir.join("tmpl");
+    let partials_dir = tmpl_dir.join("_partials");
+    fs::create_dir_all(partials_dir.join("ci"))?;
+    fs::write(partials_dir.join("license"), "// Copyright {{owner}}\n")?;
+    fs::write(
+        partials_dir.join("ci").join("checkout.yml"),
+        "- uses: actions/checkout@v4\n",
+    )?;
+    fs::write(
+        tmpl_dir.join("{{name}}.rs"),
+        "{{> license}}fn {{name}}() {}\n",
+    )?;
+    fs::write(tmpl_dir.join("ci.yml"), "steps:\n{{> ci/checkout.yml}}")?;
+    Command::cargo_bin("tempura")?
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"main","owner":"bouzuya"}"#)
+        .assert()
+        .success();
+    // <temp_dir>/ci.yml
+    // <temp_dir>/main.rs
+    assert!(!temp_dir.join("_partials").exists());
+    assert_eq!(
+        fs::read_to_string(temp_dir.join("main.rs"))?,
+        "// Copyright bouzuya\nfn main() {}\n"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.join("ci.yml"))?,
+        "steps:\n- uses: actions/checkout@v4\n"
+    );
+
+    // the partials directory can be changed in the manifest
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    let partials_dir = tmpl_dir.join("shared");
+    fs::create_dir_all(partials_dir.as_path())?;
+    fs::write(tmpl_dir.join(".tempura.json"), r#"{"partials":"shared"}"#)?;
+    fs::write(partials_dir.join("greet"), "Hello,{{name}}")?;
+    fs::write(tmpl_dir.join("{{name}}.txt"), "{{> greet}}")?;
+    Command::cargo_bin("tempura")?
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .success();
+    assert!(!temp_dir.join("shared").exists());
+    assert_eq!(
+        fs::read_to_string(temp_dir.join("World.txt"))?,
+        "Hello,World"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_error_extends_cycle() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let a_dir = temp_dir.join("a");
+    fs::create_dir_all(a_dir.as_path())?;
+    fs::write(a_dir.join(".tempura.json"), r#"{"extends":"../b"}"#)?;
+    let b_dir = temp_dir.join("b");
+    fs::create_dir_all(b_dir.as_path())?;
+    fs::write(b_dir.join(".tempura.json"), r#"{"extends":"../a"}"#)?;
+    let temp_dir = temp_dir.canonicalize()?;
+    Command::cargo_bin("tempura")?
+        .arg("a")
+        .current_dir(temp_dir.as_path())
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .failure()
+        .stderr(format!(
+            "Error: ExtendsCycle(\"{} -> {} -> {}\")\n",
+            temp_dir.join("a").display(),
+            temp_dir.join("b").display(),
+            temp_dir.join("a").display()
+        ));
+    Ok(())
+}
+
+#[test]
+fn test_example_layers() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    // <temp_dir>/base/_partials/license
+    // <temp_dir>/base/README.md
+    // <temp_dir>/base/src/main.rs
+    // <temp_dir>/http/src/main.rs
+    // <temp_dir>/http/src/http.rs
+    let base_dir = temp_dir.join("base");
+    fs::create_dir_all(base_dir.join("_partials"))?;
+    fs::create_dir_all(base_dir.join("src"))?;
+    fs::write(base_dir.join("_partials").join("license"), "// MIT\n")?;
+    fs::write(base_dir.join("README.md"), "# {{name}}\n")?;
+    fs::write(
+        base_dir.join("src").join("main.rs"),
+        "{{> license}}fn main() {}\n",
+    )?;
+    let http_dir = temp_dir.join("http");
+    fs::create_dir_all(http_dir.join("src"))?;
+    fs::write(
+        http_dir.join("src").join("main.rs"),
+        "{{> license}}mod http;\nfn main() {}\n",
+    )?;
+    fs::write(http_dir.join("src").join("http.rs"), "{{> license}}")?;
+    let out_dir = temp_dir.join("out");
+    fs::create_dir_all(out_dir.as_path())?;
+    Command::cargo_bin("tempura")?
+        .arg("../base")
+        .arg("../http")
+        .current_dir(out_dir.as_path())
+        .write_stdin(r#"{"name":"app"}"#)
+        .assert()
+        .success();
+    // <temp_dir>/out/README.md
+    // <temp_dir>/out/src/http.rs
+    // <temp_dir>/out/src/main.rs
+    assert_eq!(fs::read_to_string(out_dir.join("README.md"))?, "# app\n");
+    assert_eq!(
+        fs::read_to_string(out_dir.join("src").join("main.rs"))?,
+        "// MIT\nmod http;\nfn main() {}\n"
+    );
+    assert_eq!(
+        fs::read_to_string(out_dir.join("src").join("http.rs"))?,
+        "// MIT\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_example_layers_extends() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    // <temp_dir>/base/.tempura.json
+    // <temp_dir>/base/[[name]].txt
+    // <temp_dir>/base/common.txt
+    // <temp_dir>/cli/.tempura.json
+    // <temp_dir>/cli/common.txt
+    let base_dir = temp_dir.join("base");
+    fs::create_dir_all(base_dir.as_path())?;
+    fs::write(
+        base_dir.join(".tempura.json"),
+        r#"{"delimiters":["[[","]]"]}"#,
+    )?;
+    fs::write(base_dir.join("[[name]].txt"), "base [[name]]")?;
+    fs::write(base_dir.join("common.txt"), "base")?;
+    let cli_dir = temp_dir.join("cli");
+    fs::create_dir_all(cli_dir.as_path())?;
+    fs::write(cli_dir.join(".tempura.json"), r#"{"extends":"../base"}"#)?;
+    fs::write(cli_dir.join("common.txt"), "cli {{name}}")?;
+    let out_dir = temp_dir.join("out");
+    fs::create_dir_all(out_dir.as_path())?;
+    Command::cargo_bin("tempura")?
+        .arg("../cli")
+        .current_dir(out_dir.as_path())
+        .write_stdin(r#"{"name":"app"}"#)
+        .assert()
+        .success();
+    // <temp_dir>/out/app.txt
+    // <temp_dir>/out/common.txt
+    assert_eq!(fs::read_to_string(out_dir.join("app.txt"))?, "base app");
+    assert_eq!(fs::read_to_string(out_dir.join("common.txt"))?, "cli app");
+    assert!(!out_dir.join(".tempura.json").exists());
+    Ok(())
+}
+
+#[test]
+fn test_example_stdout() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.join("nested"))?;
+    fs::write(tmpl_dir.join("{{name}}.txt"), r#"Hello,{{name}}"#)?;
+    fs::write(tmpl_dir.join("nested").join("a.txt"), "a\n")?;
+    Command::cargo_bin("tempura")?
+        .arg("--stdout")
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .success()
+        .stdout("==> nested/a.txt <==\na\n==> World.txt <==\nHello,World\n");
+    assert!(!temp_dir.join("World.txt").exists());
+    Ok(())
+}
+
+#[test]
+fn test_example_archive() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let files: &[(&str, &[u8])] = &[
+        ("{{name}}.txt", b"Hello,{{name}}"),
+        ("{{name}}/nested.txt", b"nested {{name}}"),
+        ("_partials/p", b"partial"),
+        ("with_partial.txt", b"{{> p}}"),
+    ];
+
+    let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(
+        fs::File::create(temp_dir.join("tmpl.tar.gz"))?,
+        flate2::Compression::default(),
+    ));
+    for (path, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, format!("./{}", path), *content)?;
+    }
+    tar.into_inner()?.finish()?;
+
+    let mut zip = zip::ZipWriter::new(fs::File::create(temp_dir.join("tmpl.zip"))?);
+    for (path, content) in files {
+        zip.start_file(*path, zip::write::SimpleFileOptions::default())?;
+        zip.write_all(content)?;
+    }
+    zip.finish()?;
+
+    for archive in ["tmpl.tar.gz", "tmpl.zip"] {
+        let output_dir = temp_dir.join(archive.replace('.', "_"));
+        fs::create_dir_all(output_dir.as_path())?;
+        Command::cargo_bin("tempura")?
+            .arg(temp_dir.join(archive))
+            .current_dir(output_dir.as_path())
+            .write_stdin(r#"{"name":"World"}"#)
+            .assert()
+            .success();
+        assert_eq!(
+            fs::read_to_string(output_dir.join("World.txt"))?,
+            "Hello,World"
+        );
+        assert_eq!(
+            fs::read_to_string(output_dir.join("World").join("nested.txt"))?,
+            "nested World"
+        );
+        assert_eq!(
+            fs::read_to_string(output_dir.join("with_partial.txt"))?,
+            "partial"
+        );
+        assert!(!output_dir.join("_partials").exists());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_example_archive_invalid() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    fs::write(temp_dir.join("tmpl.zip"), "not a zip")?;
+    Command::cargo_bin("tempura")?
+        .arg("tmpl.zip")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .failure()
+        .stderr(predicates::str::starts_with(
+            "Error: ArchiveIsNotValid(\"tmpl.zip: ",
+        ));
+    Ok(())
+}
+
+#[test]
+fn test_example_archive_output() -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.join("{{name}}"))?;
+    fs::write(tmpl_dir.join("{{name}}").join("run.sh"), "echo {{name}}")?;
+    fs::set_permissions(
+        tmpl_dir.join("{{name}}").join("run.sh"),
+        fs::Permissions::from_mode(0o755),
+    )?;
+    fs::write(tmpl_dir.join("image.bin"), [0xff, 0xfe, 0x00])?;
+
+    Command::cargo_bin("tempura")?
+        .arg("--archive")
+        .arg("out.tar.gz")
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .success();
+    assert!(!temp_dir.join("World").exists());
+    let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(fs::File::open(
+        temp_dir.join("out.tar.gz"),
+    )?));
+    let mut entries = vec![];
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let mut content = vec![];
+        std::io::Read::read_to_end(&mut entry, &mut content)?;
+        entries.push((
+            entry.path()?.display().to_string(),
+            entry.header().mode()? & 0o777,
+            content,
+        ));
+    }
+    assert_eq!(
+        entries,
+        vec![
+            (
+                "image.bin".to_string(),
+                fs::metadata(tmpl_dir.join("image.bin"))?
+                    .permissions()
+                    .mode()
+                    & 0o777,
+                vec![0xff, 0xfe, 0x00]
+            ),
+            ("World/run.sh".to_string(), 0o755, b"echo World".to_vec()),
+        ]
+    );
+
+    let output = Command::cargo_bin("tempura")?
+        .arg("--archive")
+        .arg("-")
+        .arg("--archive-format")
+        .arg("zip")
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .output()?;
+    assert!(output.status.success());
+    let mut zip = zip::ZipArchive::new(std::io::Cursor::new(output.stdout))?;
+    assert_eq!(zip.len(), 2);
+    let mut file = zip.by_name("World/run.sh")?;
+    assert_eq!(file.unix_mode().map(|mode| mode & 0o777), Some(0o755));
+    let mut content = String::new();
+    std::io::Read::read_to_string(&mut file, &mut content)?;
+    assert_eq!(content, "echo World");
+    Ok(())
+}
+
+#[test]
+fn test_example_git() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let repo_dir = temp_dir.join("repo");
+    fs::create_dir_all(repo_dir.as_path())?;
+    let git = |args: &[&str]| -> anyhow::Result<()> {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(repo_dir.as_path())
+            .status()?;
+        anyhow::ensure!(status.success(), "git {:?} failed", args);
+        Ok(())
+    };
+    git(&["init", "--quiet"])?;
+    fs::write(repo_dir.join("{{name}}.txt"), "v1 {{name}}")?;
+    // the attributes for `git archive` are not applied
+    fs::write(
+        repo_dir.join(".gitattributes"),
+        "docs export-ignore\nv.txt export-subst\n",
+    )?;
+    fs::create_dir_all(repo_dir.join("docs"))?;
+    fs::write(repo_dir.join("docs").join("{{name}}.md"), "# {{name}}")?;
+    fs::write(repo_dir.join("v.txt"), "$Format:%H$")?;
+    git(&["add", "."])?;
+    git(&["commit", "--quiet", "-m", "v1"])?;
+    git(&["tag", "v1"])?;
+    fs::write(repo_dir.join("{{name}}.txt"), "v2 {{name}}")?;
+    git(&["commit", "--quiet", "-am", "v2"])?;
+    // uncommitted changes are not rendered
+    fs::write(repo_dir.join("{{name}}.txt"), "v3 {{name}}")?;
+
+    for (rev, expected) in [("v1", "v1 World"), ("HEAD", "v2 World")] {
+        let output_dir = temp_dir.join(rev);
+        fs::create_dir_all(output_dir.as_path())?;
+        Command::cargo_bin("tempura")?
+            .arg(format!("../repo#{}", rev))
+            .current_dir(output_dir.as_path())
+            .write_stdin(r#"{"name":"World"}"#)
+            .assert()
+            .success();
+        assert_eq!(fs::read_to_string(output_dir.join("World.txt"))?, expected);
+        assert_eq!(
+            fs::read_to_string(output_dir.join("docs").join("World.md"))?,
+            "# World"
+        );
+        assert_eq!(fs::read_to_string(output_dir.join("v.txt"))?, "$Format:%H$");
+        assert!(!output_dir.join(".git").exists());
+    }
+
+    Command::cargo_bin("tempura")?
+        .arg("repo#v9")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .failure()
+        .stderr("Error: GitFailed(\"ref not found: v9\")\n");
+    Ok(())
+}
+
+#[test]
+fn test_example_vcs() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.join(".git").join("objects"))?;
+    fs::write(
+        tmpl_dir.join(".git").join("objects").join("ab"),
+        [0x78, 0x9c],
+    )?;
+    fs::write(tmpl_dir.join(".git").join("HEAD"), "ref: refs/heads/main")?;
+    fs::create_dir_all(tmpl_dir.join("sub").join(".svn"))?;
+    fs::write(tmpl_dir.join("sub").join(".svn").join("entries"), "12")?;
+    fs::write(tmpl_dir.join(".gitignore"), "target/")?;
+    fs::write(tmpl_dir.join("{{name}}.txt"), "Hello,{{name}}")?;
+
+    let output_dir = temp_dir.join("default");
+    fs::create_dir_all(output_dir.as_path())?;
+    Command::cargo_bin("tempura")?
+        .arg("../tmpl")
+        .current_dir(output_dir.as_path())
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .success();
+    assert!(!output_dir.join(".git").exists());
+    assert!(!output_dir.join("sub").exists());
+    assert!(output_dir.join(".gitignore").exists());
+    assert!(output_dir.join("World.txt").exists());
+
+    let output_dir = temp_dir.join("include_vcs");
+    fs::create_dir_all(output_dir.as_path())?;
+    Command::cargo_bin("tempura")?
+        .arg("--include-vcs")
+        .arg("../tmpl")
+        .current_dir(output_dir.as_path())
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read(output_dir.join(".git").join("objects").join("ab"))?,
+        vec![0x78, 0x9c]
+    );
+    assert!(output_dir.join("sub").join(".svn").join("entries").exists());
+    Ok(())
+}
+
+#[test]
+fn test_example_answers() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.as_path())?;
+    fs::write(tmpl_dir.join("<%name%>.txt"), "Hello,<%name%>")?;
+
+    let output_dir = temp_dir.join("out1");
+    fs::create_dir_all(output_dir.as_path())?;
+    Command::cargo_bin("tempura")?
+        .arg("--save-answers")
+        .arg("--delimiters")
+        .arg("<% %>")
+        .arg("../tmpl")
+        .current_dir(output_dir.as_path())
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(output_dir.join(".tempura-answers.json"))?,
+        format!(
+            r#"{{
+  "tempura": "{}",
+  "templates": [
+    {{
+      "template": "../tmpl"
+    }}
+  ],
+  "delimiters": "<% %>",
+  "data": {{
+    "name": "World"
+  }}
+}}
+"#,
+            env!("CARGO_PKG_VERSION")
+        )
+    );
+
+    let output_dir = temp_dir.join("out2");
+    fs::create_dir_all(output_dir.as_path())?;
+    Command::cargo_bin("tempura")?
+        .arg("--from-answers")
+        .arg("../out1/.tempura-answers.json")
+        .current_dir(output_dir.as_path())
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(output_dir.join("World.txt"))?,
+        "Hello,World"
+    );
+    assert!(!output_dir.join(".tempura-answers.json").exists());
+    Ok(())
+}
+
+#[test]
+fn test_example_update() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let repo_dir = temp_dir.join("repo");
+    fs::create_dir_all(repo_dir.as_path())?;
+    let git = |args: &[&str]| -> anyhow::Result<String> {
+        let output = std::process::Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(repo_dir.as_path())
+            .output()?;
+        anyhow::ensure!(output.status.success(), "git {:?} failed", args);
+        Ok(String::from_utf8(output.stdout)?.trim().to_string())
+    };
+    git(&["init", "--quiet"])?;
+    fs::write(repo_dir.join("merged.txt"), "{{name}}\nb\nc\n")?;
+    fs::write(repo_dir.join("conflicted.txt"), "x\ny\n")?;
+    fs::write(repo_dir.join("removed.txt"), "removed\n")?;
+    fs::write(repo_dir.join("updated.txt"), "v1\n")?;
+    git(&["add", "."])?;
+    git(&["commit", "--quiet", "-m", "v1"])?;
+    git(&["tag", "v1"])?;
+
+    let project_dir = temp_dir.join("project");
+    fs::create_dir_all(project_dir.as_path())?;
+    Command::cargo_bin("tempura")?
+        .arg("--save-answers")
+        .arg("../repo#v1")
+        .current_dir(project_dir.as_path())
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .success();
+    fs::write(project_dir.join("merged.txt"), "World\nlocal\nb\nc\n")?;
+    fs::write(project_dir.join("conflicted.txt"), "local\ny\n")?;
+
+    fs::write(repo_dir.join("merged.txt"), "{{name}}\nb\nC\n")?;
+    fs::write(repo_dir.join("conflicted.txt"), "template\ny\n")?;
+    fs::remove_file(repo_dir.join("removed.txt"))?;
+    fs::write(repo_dir.join("updated.txt"), "v2\n")?;
+    fs::write(repo_dir.join("{{name}}.txt"), "added\n")?;
+    git(&["add", "."])?;
+    git(&["commit", "--quiet", "-m", "v2"])?;
+    git(&["tag", "v2"])?;
+    let v2 = git(&["rev-parse", "v2"])?;
+
+    Command::cargo_bin("tempura")?
+        .arg("update")
+        .arg("--to")
+        .arg("v2")
+        .current_dir(project_dir.as_path())
+        .assert()
+        .failure()
+        .stdout(
+            [
+                "added: World.txt",
+                "conflicted: conflicted.txt",
+                "merged: merged.txt",
+                "removed: removed.txt",
+                "updated: updated.txt",
+                "",
+            ]
+            .join("\n"),
+        )
+        .stderr("Error: MergeConflicts([\"conflicted.txt\"])\n");
+    assert_eq!(
+        fs::read_to_string(project_dir.join("conflicted.txt"))?,
+        "<<<<<<< local\nlocal\n=======\ntemplate\n>>>>>>> template\ny\n"
+    );
+    assert_eq!(
+        fs::read_to_string(project_dir.join("merged.txt"))?,
+        "World\nlocal\nb\nC\n"
+    );
+    assert!(!project_dir.join("removed.txt").exists());
+    assert_eq!(fs::read_to_string(project_dir.join("updated.txt"))?, "v2\n");
+    assert_eq!(
+        fs::read_to_string(project_dir.join("World.txt"))?,
+        "added\n"
+    );
+    let answers = fs::read_to_string(project_dir.join(".tempura-answers.json"))?;
+    assert!(answers.contains(r#""template": "../repo#v2""#));
+    assert!(answers.contains(format!(r#""commit": "{}""#, v2).as_str()));
+    Ok(())
+}
+
+#[test]
+fn test_example_update_not_versioned() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.as_path())?;
+    fs::write(tmpl_dir.join("{{name}}.txt"), "Hello,{{name}}")?;
+    let project_dir = temp_dir.join("project");
+    fs::create_dir_all(project_dir.as_path())?;
+    Command::cargo_bin("tempura")?
+        .arg("--save-answers")
+        .arg("../tmpl")
+        .current_dir(project_dir.as_path())
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .success();
+    Command::cargo_bin("tempura")?
+        .arg("update")
+        .current_dir(project_dir.as_path())
+        .assert()
+        .failure()
+        .stderr("Error: TemplateIsNotVersioned(\"../tmpl\")\n");
+    Ok(())
+}
+
+#[test]
+fn test_example_diff() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.join("src"))?;
+    fs::write(tmpl_dir.join("{{name}}.txt"), "Hello,{{name}}\n")?;
+    fs::write(tmpl_dir.join("same.txt"), "same\n")?;
+    fs::write(tmpl_dir.join("src").join("main.rs"), "fn main() {}\n")?;
+    let out_dir = temp_dir.join("out");
+    fs::create_dir_all(out_dir.as_path())?;
+    fs::write(out_dir.join("World.txt"), "Hello,world\n")?;
+    fs::write(out_dir.join("same.txt"), "same\n")?;
+    fs::write(out_dir.join("extra.txt"), "extra\n")?;
+
+    Command::cargo_bin("tempura")?
+        .arg("diff")
+        .arg("tmpl")
+        .arg("out")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"World"}"#)
+        .assert()
+        .success()
+        .stdout(
+            [
+                "--- /dev/null",
+                "+++ b/src/main.rs",
+                "@@ -0,0 +1 @@",
+                "+fn main() {}",
+                "--- a/World.txt",
+                "+++ b/World.txt",
+                "@@ -1 +1 @@",
+                "-Hello,world",
+                "+Hello,World",
+                "",
+            ]
+            .join("\n"),
+        );
+    assert_eq!(
+        fs::read_to_string(out_dir.join("World.txt"))?,
+        "Hello,world\n"
+    );
+    assert!(!out_dir.join("src").exists());
+    Ok(())
+}
+
+#[test]
+fn test_example_check() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.as_path())?;
+    fs::write(tmpl_dir.join("{{name}}.txt"), "Hello,{{name}}\n")?;
+    fs::write(tmpl_dir.join("same.txt"), "same\n")?;
+    fs::write(tmpl_dir.join("stale.txt"), "{{name}}\n")?;
+    fs::write(temp_dir.join("data.json"), r#"{"name":"World"}"#)?;
+    let out_dir = temp_dir.join("out");
+    fs::create_dir_all(out_dir.as_path())?;
+    fs::write(out_dir.join("same.txt"), "same\n")?;
+    fs::write(out_dir.join("stale.txt"), "world\n")?;
+
+    Command::cargo_bin("tempura")?
+        .arg("check")
+        .arg("--data")
+        .arg("data.json")
+        .arg("tmpl")
+        .arg("out")
+        .current_dir(temp_dir)
+        .assert()
+        .failure()
+        .stdout("stale: stale.txt\nmissing: World.txt\n")
+        .stderr("Error: OutputIsStale([\"stale.txt\", \"World.txt\"])\n");
+    assert!(!out_dir.join("World.txt").exists());
+
+    fs::write(out_dir.join("stale.txt"), "World\n")?;
+    fs::write(out_dir.join("World.txt"), "Hello,World\n")?;
+    Command::cargo_bin("tempura")?
+        .arg("check")
+        .arg("--data")
+        .arg("data.json")
+        .arg("tmpl")
+        .arg("out")
+        .current_dir(temp_dir)
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+#[test]
+fn test_example_managed_region() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.join("src"))?;
+    fs::write(
+        tmpl_dir.join("src").join("lib.rs"),
+        "// tempura:begin mods\nmod {{name}};\n// tempura:end mods\n",
+    )?;
+    let out_dir = temp_dir.join("out");
+    fs::create_dir_all(out_dir.join("src"))?;
+    fs::write(
+        out_dir.join("src").join("lib.rs"),
+        "//! hand-written\n// tempura:begin mods\nmod old;\n// tempura:end mods\n\npub fn f() {}\n",
+    )?;
+    fs::write(temp_dir.join("data.json"), r#"{"name":"world"}"#)?;
+
+    Command::cargo_bin("tempura")?
+        .arg("check")
+        .arg("--data")
+        .arg("../data.json")
+        .arg("../tmpl")
+        .arg(".")
+        .current_dir(out_dir.as_path())
+        .assert()
+        .failure()
+        .stdout("stale: src/lib.rs\n");
+    Command::cargo_bin("tempura")?
+        .arg("../tmpl")
+        .current_dir(out_dir.as_path())
+        .write_stdin(r#"{"name":"world"}"#)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(out_dir.join("src").join("lib.rs"))?,
+        "//! hand-written\n// tempura:begin mods\nmod world;\n// tempura:end mods\n\npub fn f() {}\n"
+    );
+    Command::cargo_bin("tempura")?
+        .arg("check")
+        .arg("--data")
+        .arg("../data.json")
+        .arg("../tmpl")
+        .arg(".")
+        .current_dir(out_dir.as_path())
+        .assert()
+        .success();
+    Ok(())
+}
+
+#[test]
+fn test_example_inject() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.join("src"))?;
+    fs::write(
+        tmpl_dir.join(".tempura.json"),
+        r#"{"inject":{"src/mod.rs":{"mode":"append"},"src/routes.rs":{"mode":{"before":"// routes end"}}}}"#,
+    )?;
+    fs::write(tmpl_dir.join("src").join("mod.rs"), "mod {{name}};\n")?;
+    fs::write(
+        tmpl_dir.join("src").join("routes.rs"),
+        "    route(\"/{{name}}\");\n",
+    )?;
+    fs::write(tmpl_dir.join("src").join("{{name}}.rs"), "pub fn f() {}\n")?;
+    let out_dir = temp_dir.join("out");
+    fs::create_dir_all(out_dir.join("src"))?;
+    fs::write(out_dir.join("src").join("mod.rs"), "mod a;\n")?;
+    fs::write(
+        out_dir.join("src").join("routes.rs"),
+        "fn routes() {\n    route(\"/a\");\n    // routes end\n}\n",
+    )?;
+
+    for _ in 0..2 {
+        Command::cargo_bin("tempura")?
+            .arg("../tmpl")
+            .current_dir(out_dir.as_path())
+            .write_stdin(r#"{"name":"b"}"#)
+            .assert()
+            .success();
+        fs::remove_file(out_dir.join("src").join("b.rs"))?;
+    }
+    assert_eq!(
+        fs::read_to_string(out_dir.join("src").join("mod.rs"))?,
+        "mod a;\nmod b;\n"
+    );
+    assert_eq!(
+        fs::read_to_string(out_dir.join("src").join("routes.rs"))?,
+        "fn routes() {\n    route(\"/a\");\n    route(\"/b\");\n    // routes end\n}\n"
+    );
+
+    fs::write(out_dir.join("src").join("routes.rs"), "fn routes() {}\n")?;
+    Command::cargo_bin("tempura")?
+        .arg("../tmpl")
+        .current_dir(out_dir.as_path())
+        .write_stdin(r#"{"name":"c"}"#)
+        .assert()
+        .failure()
+        .stderr("Error: AnchorNotFound(\"src/routes.rs\", \"// routes end\")\n");
+
+    // the missing files are shown as new by `diff` and reported as missing by `check`
+    let new_dir = temp_dir.join("new");
+    fs::create_dir_all(new_dir.as_path())?;
+    Command::cargo_bin("tempura")?
+        .arg("diff")
+        .arg("../tmpl")
+        .arg(".")
+        .current_dir(new_dir.as_path())
+        .write_stdin(r#"{"name":"b"}"#)
+        .assert()
+        .success()
+        .stdout(concat!(
+            "--- /dev/null\n+++ b/src/mod.rs\n@@ -0,0 +1 @@\n+mod b;\n",
+            "--- /dev/null\n+++ b/src/routes.rs\n@@ -0,0 +1 @@\n+    route(\"/b\");\n",
+            "--- /dev/null\n+++ b/src/b.rs\n@@ -0,0 +1 @@\n+pub fn f() {}\n",
+        ));
+    Command::cargo_bin("tempura")?
+        .arg("check")
+        .arg("../tmpl")
+        .arg(".")
+        .current_dir(new_dir.as_path())
+        .write_stdin(r#"{"name":"b"}"#)
+        .assert()
+        .failure()
+        .stdout("missing: src/mod.rs\nmissing: src/routes.rs\nmissing: src/b.rs\n");
+    Ok(())
+}
+
+#[test]
+fn test_example_merge() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.as_path())?;
+    fs::write(
+        tmpl_dir.join(".tempura.json"),
+        r#"{"merge":{"Cargo.toml":"keep","package.json":"override"}}"#,
+    )?;
+    fs::write(
+        tmpl_dir.join("Cargo.toml"),
+        "[package]\nname = \"{{name}}\"\n\n[dependencies]\nregex = \"1\"\n",
+    )?;
+    fs::write(
+        tmpl_dir.join("package.json"),
+        r#"{"name":"{{name}}","scripts":{"lint":"eslint"}}"#,
+    )?;
+    let out_dir = temp_dir.join("out");
+    fs::create_dir_all(out_dir.as_path())?;
+    fs::write(
+        out_dir.join("Cargo.toml"),
+        "[package]\nname = \"app\" # keep\n\n[dependencies]\nserde = \"1\"\n",
+    )?;
+    fs::write(
+        out_dir.join("package.json"),
+        "{\n  \"name\": \"app\",\n  \"scripts\": {\n    \"test\": \"jest\"\n  }\n}\n",
+    )?;
+
+    Command::cargo_bin("tempura")?
+        .arg("../tmpl")
+        .current_dir(out_dir.as_path())
+        .write_stdin(r#"{"name":"tmpl"}"#)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(out_dir.join("Cargo.toml"))?,
+        "[package]\nname = \"app\" # keep\n\n[dependencies]\nserde = \"1\"\nregex = \"1\"\n"
+    );
+    assert_eq!(
+        fs::read_to_string(out_dir.join("package.json"))?,
+        "{\n  \"name\": \"tmpl\",\n  \"scripts\": {\n    \"test\": \"jest\",\n    \"lint\": \"eslint\"\n  }\n}\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_example_front_matter() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.as_path())?;
+    fs::write(
+        tmpl_dir.join("run.sh"),
+        "---tempura\npath = \"bin/{{name}}\"\nmode = 0o755\n---\n#!/bin/sh\necho {{name}}\n",
+    )?;
+    fs::write(
+        tmpl_dir.join("Dockerfile"),
+        "---tempura\nif = \"docker\"\n---\nFROM rust\n",
+    )?;
+    fs::write(
+        tmpl_dir.join("ci.yml"),
+        "---tempura\ndelimiters = \"<% %>\"\nconflict = \"overwrite\"\n---\nrun: ${{ env.<%name%> }}\n",
+    )?;
+    fs::write(
+        tmpl_dir.join("README.md"),
+        "---tempura\nconflict = \"skip\"\n---\n# {{name}}\n",
+    )?;
+    let out_dir = temp_dir.join("out");
+    fs::create_dir_all(out_dir.as_path())?;
+    fs::write(out_dir.join("ci.yml"), "old\n")?;
+    fs::write(out_dir.join("README.md"), "# hand-written\n")?;
+
+    Command::cargo_bin("tempura")?
+        .arg("../tmpl")
+        .current_dir(out_dir.as_path())
+        .write_stdin(r#"{"name":"app","docker":"false"}"#)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(out_dir.join("bin").join("app"))?,
+        "#!/bin/sh\necho app\n"
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(
+            fs::metadata(out_dir.join("bin").join("app"))?
+                .permissions()
+                .mode()
+                & 0o111,
+            0o111
+        );
+    }
+    assert!(!out_dir.join("Dockerfile").exists());
+    assert!(!out_dir.join("run.sh").exists());
+    assert_eq!(
+        fs::read_to_string(out_dir.join("ci.yml"))?,
+        "run: ${{ env.app }}\n"
+    );
+    assert_eq!(
+        fs::read_to_string(out_dir.join("README.md"))?,
+        "# hand-written\n"
+    );
+
+    fs::write(
+        tmpl_dir.join("run.sh"),
+        "---tempura\npath = \"../{{name}}\"\n---\n",
+    )?;
+    Command::cargo_bin("tempura")?
+        .arg("../tmpl")
+        .current_dir(out_dir.as_path())
+        .write_stdin(r#"{"name":"app"}"#)
+        .assert()
+        .failure()
+        .stderr(predicates::str::contains("FrontMatterIsNotValid"));
+    Ok(())
+}
+
+#[test]
+fn test_example_file_blocks() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    fs::create_dir_all(tmpl_dir.join("src"))?;
+    fs::write(
+        tmpl_dir.join("src").join("{{name}}.rs"),
+        "pub fn {{name}}() {}\n{{#file \"src/{{name}}_test.rs\"}}\n#[test]\nfn test_{{name}}() {}\n{{/file}}\n",
+    )?;
+    fs::write(
+        tmpl_dir.join("handler"),
+        "{{#file \"handlers/{{name}}.txt\"}}\nhandler {{name}}\n{{/file}}\n{{#file \"routes/{{name}}.txt\"}}\nroute {{name}}\n{{/file}}\n",
+    )?;
+
+    Command::cargo_bin("tempura")?
+        .arg("--stdout")
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"users"}"#)
+        .assert()
+        .success()
+        .stdout(concat!(
+            "==> handlers/users.txt <==\nhandler users\n",
+            "==> routes/users.txt <==\nroute users\n",
+            "==> src/users.rs <==\npub fn users() {}\n",
+            "==> src/users_test.rs <==\n#[test]\nfn test_users() {}\n",
+        ));
+
+    fs::write(
+        tmpl_dir.join("handler"),
+        "{{#file \"../{{name}}.txt\"}}\n{{/file}}\n",
+    )?;
+    Command::cargo_bin("tempura")?
+        .arg("--stdout")
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"users"}"#)
+        .assert()
+        .failure()
+        .stderr("Error: FileBlockIsNotValid(\"handler: invalid path: ../users.txt\")\n");
+
+    fs::write(
+        tmpl_dir.join("handler"),
+        "Docs: {{#raw}}{{#file \"x.txt\"}}body{{/file}}{{/raw}}\n{{- #file \"{{name}}.txt\" -}}\n  {{name}}  \n{{- /file}}\n",
+    )?;
+    Command::cargo_bin("tempura")?
+        .arg("--stdout")
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"users"}"#)
+        .assert()
+        .success()
+        .stdout(concat!(
+            "==> handler <==\nDocs: {{#file \"x.txt\"}}body{{/file}}\n",
+            "==> users.txt <==\nusers\n",
+            "==> src/users.rs <==\npub fn users() {}\n",
+            "==> src/users_test.rs <==\n#[test]\nfn test_users() {}\n",
+        ));
+    Ok(())
+}
+
+#[test]
+fn test_example_path_filter() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let tmpl_dir = temp_dir.join("tmpl");
+    let java_dir = tmpl_dir.join("src").join("{{package|path}}");
+    fs::create_dir_all(java_dir.as_path())?;
+    fs::write(
+        java_dir.join("{{name}}.java"),
+        "package {{package_name}}; // {{package|path}}\n",
+    )?;
+
+    Command::cargo_bin("tempura")?
+        .arg("--stdout")
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(
+            r#"{"name":"App","package":"com/example/app","package_name":"com.example.app"}"#,
+        )
+        .assert()
+        .success()
+        .stdout(
+            "==> src/com/example/app/App.java <==\npackage com.example.app; // com/example/app\n",
+        );
+
+    Command::cargo_bin("tempura")?
+        .arg("--stdout")
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"App","package":"com/../../etc","package_name":"com"}"#)
+        .assert()
+        .failure()
+        .stderr("Error: VariableIsNotValidPath(\"package\", \"com/../../etc\")\n");
+
+    Command::cargo_bin("tempura")?
+        .arg("--stdout")
+        .arg("tmpl")
+        .current_dir(temp_dir)
+        .write_stdin(r#"{"name":"a/App","package":"com","package_name":"com"}"#)
+        .assert()
+        .failure()
+        .stderr(
+            "Error: VariableContainsPathSeparator(\"src/{{package|path}}/{{name}}.java\", \"src/{{package|path}}/a/App.java\")\n",
+        );
+    Ok(())
+}
+
+#[test]
+fn test_example_subcommand_name_template() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    for name in ["check", "diff", "update"] {
+        let tmpl_dir = temp_dir.join(name);
+        fs::create_dir_all(tmpl_dir.as_path())?;
+        fs::write(tmpl_dir.join("{{name}}.txt"), format!("{}\n", name))?;
+
+        // the subcommand wins over the directory
+        Command::cargo_bin("tempura")?
+            .arg(name)
+            .current_dir(temp_dir)
+            .assert()
+            .failure();
+
+        Command::cargo_bin("tempura")?
+            .arg(format!("./{}", name))
+            .arg("--stdout")
+            .current_dir(temp_dir)
+            .write_stdin(r#"{"name":"a"}"#)
+            .assert()
+            .success()
+            .stdout(format!("==> a.txt <==\n{}\n", name));
+
+        Command::cargo_bin("tempura")?
+            .arg("--stdout")
+            .arg("--")
+            .arg(name)
+            .current_dir(temp_dir)
+            .write_stdin(r#"{"name":"a"}"#)
+            .assert()
+            .success()
+            .stdout(format!("==> a.txt <==\n{}\n", name));
+    }
+    Command::cargo_bin("tempura")?
+        .arg("check")
+        .current_dir(temp_dir)
+        .assert()
+        .failure()
+        .stderr("Error: NoArguments\n");
+    assert!(!temp_dir.join("a.txt").exists());
+    Ok(())
+}
+
+#[test]
+fn test_example_update_existing_files() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let repo_dir = temp_dir.join("repo");
+    fs::create_dir_all(repo_dir.as_path())?;
+    let git = |args: &[&str]| -> anyhow::Result<()> {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(repo_dir.as_path())
+            .status()?;
+        anyhow::ensure!(status.success(), "git {:?} failed", args);
+        Ok(())
+    };
+    git(&["init", "--quiet"])?;
+    fs::write(
+        repo_dir.join(".tempura.json"),
+        r#"{"inject":{"mod.rs":{"mode":"append"}},"merge":{"Cargo.toml":"keep"}}"#,
+    )?;
+    fs::write(repo_dir.join("mod.rs"), "mod {{name}};\n")?;
+    fs::write(
+        repo_dir.join("lib.rs"),
+        "// tempura:begin mods\nmod {{name}};\n// tempura:end mods\n",
+    )?;
+    fs::write(
+        repo_dir.join("Cargo.toml"),
+        "[dependencies]\nserde = \"1\"\n",
+    )?;
+    git(&["add", "."])?;
+    git(&["commit", "--quiet", "-m", "v1"])?;
+    git(&["tag", "v1"])?;
+
+    let project_dir = temp_dir.join("project");
+    fs::create_dir_all(project_dir.as_path())?;
+    fs::write(project_dir.join("mod.rs"), "//! hand-written\nmod a;\n")?;
+    fs::write(
+        project_dir.join("lib.rs"),
+        "//! hand-written\n// tempura:begin mods\n// tempura:end mods\nfn f() {}\n",
+    )?;
+    fs::write(
+        project_dir.join("Cargo.toml"),
+        "[package]\nname = \"app\"\n\n[dependencies]\n",
+    )?;
+    Command::cargo_bin("tempura")?
+        .arg("--save-answers")
+        .arg("../repo#v1")
+        .current_dir(project_dir.as_path())
+        .write_stdin(r#"{"name":"b"}"#)
+        .assert()
+        .success();
+
+    fs::write(repo_dir.join("mod.rs"), "pub mod {{name}};\n")?;
+    fs::write(
+        repo_dir.join("lib.rs"),
+        "// tempura:begin mods\npub mod {{name}};\n// tempura:end mods\n",
+    )?;
+    fs::write(
+        repo_dir.join("Cargo.toml"),
+        "[dependencies]\nserde = \"1\"\nregex = \"1\"\n",
+    )?;
+    git(&["add", "."])?;
+    git(&["commit", "--quiet", "-m", "v2"])?;
+    git(&["tag", "v2"])?;
+
+    Command::cargo_bin("tempura")?
+        .arg("update")
+        .arg("--to")
+        .arg("v2")
+        .current_dir(project_dir.as_path())
+        .assert()
+        .success()
+        .stdout("updated: Cargo.toml\nupdated: lib.rs\nupdated: mod.rs\n");
+    assert_eq!(
+        fs::read_to_string(project_dir.join("mod.rs"))?,
+        "//! hand-written\nmod a;\npub mod b;\n"
+    );
+    assert_eq!(
+        fs::read_to_string(project_dir.join("lib.rs"))?,
+        "//! hand-written\n// tempura:begin mods\npub mod b;\n// tempura:end mods\nfn f() {}\n"
+    );
+    assert_eq!(
+        fs::read_to_string(project_dir.join("Cargo.toml"))?,
+        "[package]\nname = \"app\"\n\n[dependencies]\nserde = \"1\"\nregex = \"1\"\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_example_update_conflict_policy() -> anyhow::Result<()> {
+    let temp_dir = TempDir::new("tempura")?;
+    let temp_dir = temp_dir.path();
+    let repo_dir = temp_dir.join("repo");
+    fs::create_dir_all(repo_dir.as_path())?;
+    let git = |args: &[&str]| -> anyhow::Result<()> {
+        let status = std::process::Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(repo_dir.as_path())
+            .status()?;
+        anyhow::ensure!(status.success(), "git {:?} failed", args);
+        Ok(())
+    };
+    git(&["init", "--quiet"])?;
+    fs::write(
+        repo_dir.join("README"),
+        "---tempura\nconflict = \"skip\"\n---\n# {{name}}\n",
+    )?;
+    fs::write(
+        repo_dir.join("NOTES"),
+        "---tempura\nconflict = \"overwrite\"\n---\nnotes\n",
+    )?;
+    git(&["add", "."])?;
+    git(&["commit", "--quiet", "-m", "v1"])?;
+    git(&["tag", "v1"])?;
+
+    let project_dir = temp_dir.join("project");
+    fs::create_dir_all(project_dir.as_path())?;
+    Command::cargo_bin("tempura")?
+        .arg("--save-answers")
+        .arg("../repo#v1")
+        .current_dir(project_dir.as_path())
+        .write_stdin(r#"{"name":"app"}"#)
+        .assert()
+        .success();
+    fs::write(project_dir.join("README"), "# app\n\nhand-written\n")?;
+    fs::write(project_dir.join("NOTES"), "local notes\n")?;
+
+    fs::write(
+        repo_dir.join("README"),
+        "---tempura\nconflict = \"skip\"\n---\n# {{name}} (v2)\n",
+    )?;
+    fs::write(
+        repo_dir.join("NOTES"),
+        "---tempura\nconflict = \"overwrite\"\n---\nnotes v2\n",
+    )?;
+    git(&["add", "."])?;
+    git(&["commit", "--quiet", "-m", "v2"])?;
+    git(&["tag", "v2"])?;
+
+    Command::cargo_bin("tempura")?
+        .arg("update")
+        .arg("--to")
+        .arg("v2")
+        .current_dir(project_dir.as_path())
+        .assert()
+        .success()
+        .stdout("updated: NOTES\nskipped: README\n");
+    assert_eq!(
+        fs::read_to_string(project_dir.join("README"))?,
+        "# app\n\nhand-written\n"
+    );
+    assert_eq!(fs::read_to_string(project_dir.join("NOTES"))?, "notes v2\n");
+    Ok(())
+}